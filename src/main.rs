@@ -1,22 +1,40 @@
 use anyhow::{Context, Result};
 use crossterm::{
-    event::{self, Event, KeyCode, KeyEventKind},
+    event::{
+        self, DisableBracketedPaste, EnableBracketedPaste, Event, KeyCode, KeyEventKind,
+        KeyModifiers,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use dirs::home_dir;
 use ratatui::{
     backend::CrosstermBackend,
     layout::{Constraint, Direction, Layout, Rect},
     prelude::Stylize,
     style::{Color, Modifier, Style},
+    symbols,
     text::{Line, Span},
-    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap},
+    widgets::{
+        Block, Borders, Clear, Gauge, List, ListItem, ListState, Paragraph, Scrollbar,
+        ScrollbarOrientation, ScrollbarState, Wrap,
+    },
     Frame, Terminal,
 };
 
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::IsTerminal;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
 use std::path::Path;
 use std::{env, fs, io, process::Command};
+use wait_timeout::ChildExt;
+
+/// Exports above this size are highlighted in the summary.
+const LARGE_EXPORT_BYTES: u64 = 500 * 1024 * 1024;
+
+const SELECTING_HINT: &str =
+    "Space to toggle, Enter to continue, / to filter, r to restore a theme, ? for help";
 
 #[derive(Debug, Clone)]
 pub struct ThemeComponent {
@@ -24,9 +42,57 @@ pub struct ThemeComponent {
     pub source_paths: Vec<String>,
     pub description: String,
     pub checked: bool,
-    pub current_style: Option<String>,
+    pub current_style: Option<Detection>,
+    /// Added from the TUI rather than built in or configured.
+    pub runtime: bool,
+    /// Glob patterns for file and folder names to leave out of the copy.
+    pub exclude: Vec<String>,
+    /// Also export the parents icon and cursor themes inherit from.
+    pub follow_inherits: bool,
+    /// Entries of `source_paths` unticked in the path picker.
+    pub excluded_sources: Vec<String>,
+    /// Group the component is listed under, toggled together with `g`.
+    pub category: String,
+}
+
+/// Order of the built-in groups in the list; other groups follow in the
+/// order their first component appears.
+const CATEGORIES: &[&str] = &["Desktop Environment", "Terminal", "Shell", "Boot/Login"];
+
+/// The group a built-in component is listed under; anything added by the
+/// user is "Custom" unless the config says otherwise.
+fn default_category(name: &str) -> &'static str {
+    match name {
+        "Terminal Themes" | "Konsole Profiles" => "Terminal",
+        "Prompt Theme" => "Shell",
+        "Splash Screen" | "SDDM Theme" => "Boot/Login",
+        "Global Theme"
+        | "GTK Themes"
+        | "Icons"
+        | "Cursors"
+        | "Fonts"
+        | "Qt/KDE Styles"
+        | "Application Style"
+        | "Colors Schemes"
+        | "Window Decorations"
+        | "KWin Scripts"
+        | "Desktop Effects"
+        | "Window Manager Themes"
+        | "Plasma Widgets"
+        | "Panel Layout"
+        | "Wallpapers"
+        | "Launcher Themes"
+        | "Notification Themes"
+        | "Status Bar"
+        | "GNOME Shell"
+        | "Xfce Settings" => "Desktop Environment",
+        _ => "Custom",
+    }
 }
 
+/// Generated files that are rebuilt on the target machine anyway.
+const DEFAULT_EXCLUDES: &[&str] = &["*.cache", "icon-theme.cache", "__pycache__"];
+
 impl ThemeComponent {
     pub fn new(name: &str, source_paths: Vec<&str>, description: &str) -> Self {
         let mut component = Self {
@@ -35,30 +101,344 @@ impl ThemeComponent {
             description: description.to_string(),
             checked: false,
             current_style: None,
+            runtime: false,
+            exclude: DEFAULT_EXCLUDES.iter().map(|s| s.to_string()).collect(),
+            follow_inherits: true,
+            excluded_sources: Vec::new(),
+            category: default_category(name).to_string(),
         };
 
         component.current_style = component.detect_current_style();
+        match &component.current_style {
+            Some(style) => log::debug!(
+                "{}: detected {} ({})",
+                component.name,
+                style.value,
+                style.source
+            ),
+            None => log::debug!("{}: detected nothing", component.name),
+        }
         component
     }
 
-    fn detect_current_style(&self) -> Option<String> {
+    /// Name of the folder this component is stored under in an exported theme.
+    pub fn dir_name(&self) -> String {
+        self.name.replace(&[' ', '/'][..], "_")
+    }
+
+    /// Name the configured `source` is stored under in `dir_name`: its own
+    /// name, or, when another source path of the component has the same
+    /// name, the configured path without `~/` (or `/`) and with `/` as `_`.
+    /// So `~/.local/share/themes/` and `/usr/share/themes/` become
+    /// `.local_share_themes` and `usr_share_themes` instead of being merged,
+    /// wherever the XDG variables point.
+    pub fn export_name(&self, source: &str) -> std::ffi::OsString {
+        let path = expand_path(source);
+        let Some(name) = path.file_name() else {
+            return source.into();
+        };
+        let clashes = self
+            .source_paths
+            .iter()
+            .filter(|other| expand_path(other).file_name() == Some(name))
+            .count();
+        if clashes < 2 {
+            return name.to_owned();
+        }
+        Path::new(source.trim_start_matches('~'))
+            .components()
+            .filter_map(|part| match part {
+                std::path::Component::Normal(part) => Some(part.to_string_lossy()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("_")
+            .into()
+    }
+
+    /// The source path an entry of an exported component folder belongs to.
+    /// Exports from before `export_name` stored clashing sources under their
+    /// plain name; those go to the user-level path.
+    pub fn restore_source(&self, entry_name: &std::ffi::OsStr) -> Option<&String> {
+        if let Some(source) = self
+            .source_paths
+            .iter()
+            .find(|source| self.export_name(source) == entry_name)
+        {
+            return Some(source);
+        }
+        let mut candidates: Vec<&String> = self
+            .source_paths
+            .iter()
+            .filter(|source| expand_path(source).file_name() == Some(entry_name))
+            .collect();
+        candidates.sort_by_key(|source| !source.starts_with('~'));
+        candidates.first().copied()
+    }
+
+    /// Where the exported `entry` of this component's folder is restored to,
+    /// as a configured path still to be expanded. Font files resolved with
+    /// fc-match have no source path and go to the user's font folder.
+    pub fn restore_destination(&self, entry: &Path) -> Option<String> {
+        let entry_name = entry.file_name()?;
+        match self.restore_source(entry_name) {
+            Some(source) => Some(source.trim_end_matches('/').to_string()),
+            None if self.name == "Fonts" && entry.is_file() => Some(format!(
+                "~/.local/share/fonts/{}",
+                entry_name.to_string_lossy()
+            )),
+            None => None,
+        }
+    }
+
+    /// Matcher for `exclude` plus the `extra` patterns. Invalid patterns are
+    /// rejected when the config is loaded, so any that slip through here are
+    /// ignored.
+    pub fn exclude_set(&self, extra: &[&str]) -> GlobSet {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in self
+            .exclude
+            .iter()
+            .map(String::as_str)
+            .chain(extra.iter().copied())
+        {
+            if let Ok(glob) = Glob::new(pattern) {
+                builder.add(glob);
+            }
+        }
+        builder.build().unwrap_or_else(|_| GlobSet::empty())
+    }
+
+    /// The source paths that are exported, leaving out the unticked ones.
+    pub fn active_sources(&self) -> impl Iterator<Item = &String> {
+        self.source_paths
+            .iter()
+            .filter(|path| !self.excluded_sources.contains(path))
+    }
+
+    /// Ticks or unticks one of `source_paths` for export.
+    pub fn toggle_source(&mut self, index: usize) {
+        let Some(path) = self.source_paths.get(index) else {
+            return;
+        };
+        match self.excluded_sources.iter().position(|p| p == path) {
+            Some(pos) => {
+                self.excluded_sources.remove(pos);
+            }
+            None => self.excluded_sources.push(path.clone()),
+        }
+    }
+
+    /// Command-line identifier, e.g. "GTK Themes" -> "gtk-themes".
+    pub fn short_name(&self) -> String {
+        short_name_of(&self.name)
+    }
+
+    fn detect_current_style(&self) -> Option<Detection> {
         match self.name.as_str() {
-            "GTK Themes" => detect_gtk_theme(),
+            "Global Theme" => detect_global_theme(),
+            "GTK Themes" => with_color_preference(detect_gtk_theme()),
             "Icons" => detect_icon_theme(),
             "Cursors" => detect_cursor_theme(),
             "Qt/KDE Styles" => detect_qt_style(),
-            "Application Style" => detect_application_style(),
+            "Application Style" => with_color_preference(detect_application_style()),
             "Colors Schemes" => detect_color_scheme(),
             "Window Decorations" => detect_window_decorations(),
+            "KWin Scripts" => detect_kwin_scripts(),
+            "Desktop Effects" => detect_desktop_effects(),
             "Splash Screen" => detect_splash_screen(),
             "SDDM Theme" => detect_sddm_theme(),
             "Terminal Themes" => detect_terminal_theme(),
             "Window Manager Themes" => detect_wm_theme(),
             "Shell Themes" => detect_shell_theme(),
+            "Prompt Theme" => detect_prompt_theme(),
             "Fonts" => detect_font_theme(),
+            "Konsole Profiles" => detect_konsole_theme(),
+            "Plasma Widgets" => detect_plasma_widgets(),
+            "Panel Layout" => detect_panel_layout(),
+            "Wallpapers" => detect_wallpaper(),
+            "Launcher Themes" => detect_launcher_theme(),
+            "Notification Themes" => detect_notification_theme(),
+            "Status Bar" => detect_bar_theme(),
+            "GNOME Shell" => detect_gnome_shell_theme(),
+            "Xfce Settings" => detect_xfce_theme(),
+            _ => None,
+        }
+    }
+
+    /// The entries below the source `path` to copy, or `None` to copy all of
+    /// it. Cursors share their folders with icon themes, so only the cursor
+    /// files of each theme are taken and the icons are left to "Icons"; the
+    /// same goes for Shell themes inside GTK themes and Plasma splash screens
+    /// inside Look and Feel packages.
+    fn selected_entries(&self, path: &Path) -> Option<Vec<std::path::PathBuf>> {
+        let is_theme_folder = path
+            .file_name()
+            .is_some_and(|name| name == ".themes" || name == "themes");
+        let is_look_and_feel = path.file_name().is_some_and(|name| name == "look-and-feel");
+        match self.name.as_str() {
+            "Splash Screen" if is_look_and_feel => Some(
+                fs::read_dir(path)
+                    .map(|entries| {
+                        entries
+                            .flatten()
+                            .map(|entry| entry.path().join("contents/splash"))
+                            .filter(|splash| splash.is_dir())
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+            ),
+            "GNOME Shell" if is_theme_folder => Some(
+                fs::read_dir(path)
+                    .map(|entries| {
+                        entries
+                            .flatten()
+                            .map(|entry| entry.path().join("gnome-shell"))
+                            .filter(|shell| shell.is_dir())
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+            ),
+            "Cursors" => Some(
+                fs::read_dir(path)
+                    .map(|entries| {
+                        entries
+                            .flatten()
+                            .map(|entry| entry.path())
+                            .filter(|theme| theme.join("cursors").is_dir())
+                            .flat_map(|theme| cursor_theme_files(&theme))
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+            ),
             _ => None,
         }
     }
+
+    /// Whether `path` can be moved into the theme and linked back: only the
+    /// user's own files, and only sources the component takes as a whole.
+    /// Sources that are already links (e.g. managed by stow) are copied, and
+    /// so are sources holding anything `exclude` leaves out, since moving
+    /// them would take the caches or another component's files along.
+    fn can_link(&self, path: &Path, exclude: &GlobSet) -> bool {
+        // Without the trailing slash, so a link isn't followed
+        let entry: std::path::PathBuf = path.components().collect();
+        path.starts_with(get_user_home_dir())
+            && self.selected_entries(path).is_none()
+            && fs::symlink_metadata(entry).is_ok_and(|metadata| !metadata.file_type().is_symlink())
+            && !contains_excluded(path, exclude)
+    }
+
+    /// Everything an export of this component copies, in order: the active
+    /// source paths, then the referenced files outside them. `create_theme`,
+    /// `dry_run` and `diff_export` all work from this list.
+    fn export_plan(&self) -> Vec<ExportSource> {
+        let dir = std::path::PathBuf::from(self.dir_name());
+        let mut plan: Vec<ExportSource> = self
+            .active_sources()
+            .map(|configured| {
+                let path = expand_path(configured);
+                ExportSource {
+                    destination: dir.join(self.export_name(configured)),
+                    entries: self.selected_entries(&path),
+                    configured: Some(configured.clone()),
+                    path,
+                }
+            })
+            .collect();
+        // Referenced files inside an unticked source path stay out with it
+        let excluded: Vec<std::path::PathBuf> = self
+            .excluded_sources
+            .iter()
+            .map(|p| expand_path(p))
+            .collect();
+        for path in self.referenced_files() {
+            if plan.iter().any(|source| path.starts_with(&source.path))
+                || excluded.iter().any(|source| path.starts_with(source))
+            {
+                continue;
+            }
+            // Inherited themes are whole icon themes; Cursors only takes
+            // their cursor files, like it does from its source paths
+            let entries =
+                (self.name == "Cursors" && path.is_dir()).then(|| cursor_theme_files(&path));
+            plan.push(ExportSource {
+                destination: dir.join(path.file_name().unwrap_or_default()),
+                entries,
+                configured: None,
+                path,
+            });
+        }
+        plan
+    }
+
+    /// Themes that the icon or cursor themes in the source folders inherit
+    /// from (`Inherits=` in index.theme, followed up the chain) and that are
+    /// installed somewhere else, since the export renders with missing icons
+    /// without them.
+    fn inherited_themes(&self) -> Vec<std::path::PathBuf> {
+        let cursors = self.name == "Cursors";
+        let is_theme = |dir: &Path| {
+            dir.join("index.theme").is_file() && (!cursors || dir.join("cursors").is_dir())
+        };
+        let roots: Vec<std::path::PathBuf> =
+            self.active_sources().map(|p| expand_path(p)).collect();
+
+        let mut seen = std::collections::HashSet::new();
+        let mut pending = Vec::new();
+        for root in &roots {
+            for entry in fs::read_dir(root).into_iter().flatten().flatten() {
+                if is_theme(&entry.path()) {
+                    seen.insert(entry.file_name().to_string_lossy().to_string());
+                    pending.extend(theme_inherits(&entry.path()));
+                }
+            }
+        }
+
+        let mut inherited = Vec::new();
+        while let Some(name) = pending.pop() {
+            // Every system has hicolor, it is the fallback for all themes
+            if name == "hicolor" || !seen.insert(name.clone()) {
+                continue;
+            }
+            let Some(dir) = icon_theme_dirs()
+                .into_iter()
+                .map(|dir| dir.join(&name))
+                .find(|dir| dir.join("index.theme").is_file())
+            else {
+                log::debug!("{}: inherited theme {} not found", self.name, name);
+                continue;
+            };
+            pending.extend(theme_inherits(&dir));
+            if is_theme(&dir) && !roots.iter().any(|root| dir.starts_with(root)) {
+                inherited.push(dir);
+            }
+        }
+        inherited
+    }
+
+    /// Files outside `source_paths` that the active configuration points at and
+    /// that must be copied for the component to be complete.
+    fn referenced_files(&self) -> Vec<std::path::PathBuf> {
+        match self.name.as_str() {
+            "Konsole Profiles" => konsole_referenced_files(),
+            "Wallpapers" => active_wallpaper_path()
+                .into_iter()
+                .chain(wallpaper_slideshow_paths())
+                .collect(),
+            "Launcher Themes" => rofi_theme_path().into_iter().collect(),
+            "Fonts" => configured_font_files(),
+            "SDDM Theme" => sddm_background_path().into_iter().collect(),
+            "Terminal Themes" => alacritty_theme_files(),
+            "Prompt Theme" => starship_config_path().into_iter().collect(),
+            "Colors Schemes" => kde_color_scheme_name()
+                .and_then(|scheme| color_scheme_file(&scheme))
+                .into_iter()
+                .collect(),
+            "Icons" | "Cursors" if self.follow_inherits => self.inherited_themes(),
+            _ => Vec::new(),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -72,941 +452,7456 @@ pub struct App {
     pub theme_directory: String,
     pub directory_entries: Vec<String>,
     pub directory_selected: usize,
+    /// Why the theme directory couldn't be listed, shown instead of its entries.
+    pub directory_error: Option<String>,
+    /// First visible row of the lists, kept between frames so scrolling is
+    /// stable; updated while drawing.
+    pub selection_offset: std::cell::Cell<usize>,
+    pub directory_offset: std::cell::Cell<usize>,
+    pub new_directory_name: String,
+    pub new_component_name: String,
+    pub new_component_path: String,
+    pub path_input: String,
+    pub pending_create_path: Option<std::path::PathBuf>,
+    pub create_archive: bool,
+    /// Move home sources into the theme and leave symlinks behind (stow-style)
+    /// instead of copying them.
+    pub link_sources: bool,
+    /// Compare every copied file with its source once the export is done.
+    pub verify: bool,
+    /// Hardlink files with the same content within a component's folder.
+    pub dedupe: bool,
+    /// `git add` the export when it lands inside a git work tree.
+    pub git_add: bool,
+    /// Serialization used for the manifest, set with `--output-format`.
+    pub manifest_format: ManifestFormat,
+    /// Copy the entries the user can't read with `sudo cp` instead of
+    /// skipping them.
+    pub sudo_unreadable: bool,
+    pub restore_entries: Vec<String>,
+    pub restore_selected: usize,
+    pub restore_plan: Vec<RestoreAction>,
+    /// Components of `restore_plan` unticked on the confirmation screen.
+    pub restore_skipped: Vec<String>,
+    pub restore_component: usize,
+    pub restore_offset: std::cell::Cell<usize>,
+    pub exit_report: Vec<String>,
+    pub dry_run_report: Vec<DryRunEntry>,
+    pub diff_report: Vec<DiffEntry>,
+    pub diff_scroll: u16,
+    /// Selection left by an earlier run, offered by `Mode::ResumeSession`.
+    pub saved_session: Option<Session>,
+    /// The finished export shown by `Mode::Results`.
+    pub export_result: Option<ExportResult>,
+    pub results_scroll: u16,
+    /// Bytes the checked components will copy, computed on entering the summary.
+    pub estimated_size: u64,
+    /// Whether Flatpak apps can use the user's themes, when any are installed.
+    pub flatpak_note: Option<String>,
+    pub filter: String,
+    pub filtering: bool,
+    /// Key help popup drawn over the current mode.
+    pub show_help: bool,
+    /// Names of the checked components before each selection change, newest
+    /// last, so `u` can step back. Names keep it right across reordering.
+    pub selection_history: Vec<Vec<String>>,
+    /// Highlighted row of the source path picker.
+    pub source_selected: usize,
+    /// Saved selections, loaded when the profile list is opened.
+    pub profiles: Vec<Profile>,
+    pub profile_selected: usize,
+    pub profile_name: String,
 }
 
 #[derive(Debug, PartialEq)]
 pub enum Mode {
     Selecting,
+    SourcePaths,
     Naming,
     DirectorySelection,
+    NewDirectory,
+    AddComponentName,
+    AddComponentPath,
+    EditPath,
     Summary,
+    DryRun,
+    Diff,
     PermissionCheck,
+    Restore,
+    RestoreConfirm,
+    Profiles,
+    ProfileName,
+    Results,
+    ResumeSession,
 }
 
-#[derive(Debug)]
-pub struct PermissionIssue {
-    pub component: String,
-    pub path: String,
-    pub issue_type: PermissionIssueType,
-}
-
-#[derive(Debug)]
-pub enum PermissionIssueType {
-    NoReadAccess,
-    NoWriteAccess,
-    SudoRequired,
+/// Serialization of the manifest, chosen with `--output-format`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ManifestFormat {
+    #[default]
+    Json,
+    Toml,
+    Yaml,
 }
 
-impl App {
-    pub fn new() -> Self {
-        let components = vec![
-            ThemeComponent::new(
-                "GTK Themes",
-                vec!["~/.themes/", "~/.local/share/themes/", "/usr/share/themes/"],
-                "GTK2/GTK3 theme files",
-            ),
-            ThemeComponent::new(
-                "Icons",
-                vec!["~/.icons/", "~/.local/share/icons/", "/usr/share/icons/"],
-                "Icon themes",
-            ),
-            ThemeComponent::new(
-                "Cursors",
-                vec!["~/.icons/", "~/.local/share/icons/", "/usr/share/icons/"],
-                "Mouse cursor themes",
-            ),
-            ThemeComponent::new("Qt/KDE Styles", vec!["~/.config/"], "Qt5/Qt6 styles"),
-            ThemeComponent::new(
-                "Application Style",
-                vec!["~/.config/", "/etc/xdg/"],
-                "Current desktop application style (Oxygen, Edge Runner, etc.)",
-            ),
-            ThemeComponent::new(
-                "Colors Schemes",
-                vec!["~/.local/share/color-schemes/"],
-                "KDE color schemes",
-            ),
-            ThemeComponent::new(
-                "Window Decorations",
-                vec![
-                    "~/.config/kwinrc",
-                    "~/.config/awesome/",
-                    "~/.config/i3/",
-                    "~/.config/openbox/",
-                    "~/.config/bspwm/",
-                    "/usr/share/kde4/config/",
-                ],
-                "Window manager decorations and borders",
-            ),
-            ThemeComponent::new(
-                "Splash Screen",
-                vec![
-                    "/usr/share/plymouth/themes/",
-                    "/boot/grub/themes/",
-                    "/etc/alternatives/",
-                    "~/.config/plymouth/",
-                ],
-                "Boot splash screen and login animations",
-            ),
-            ThemeComponent::new(
-                "SDDM Theme",
-                vec!["/usr/share/sddm/themes/"],
-                "SDDM login manager theme",
-            ),
-            ThemeComponent::new(
-                "Terminal Themes",
-                vec!["~/.config/alacritty/", "~/.config/kitty/"],
-                "Terminal themes",
-            ),
-        ];
-
-        let default_theme_dir = if let Some(home) = home_dir() {
-            home.join("CustomThemes").to_string_lossy().to_string()
-        } else {
-            "./CustomThemes".to_string()
-        };
+impl std::str::FromStr for ManifestFormat {
+    type Err = anyhow::Error;
 
-        Self {
-            components,
-            selected: 0,
-            theme_name: String::new(),
-            mode: Mode::Selecting,
-            message: "Space to toggle, Enter to continue".to_string(),
-            permission_issues: Vec::new(),
-            theme_directory: default_theme_dir,
-            directory_entries: Vec::new(),
-            directory_selected: 0,
+    fn from_str(format: &str) -> Result<Self> {
+        match format.to_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "toml" => Ok(Self::Toml),
+            "yaml" | "yml" => Ok(Self::Yaml),
+            _ => anyhow::bail!("Unknown output format {} (json, toml or yaml)", format),
         }
     }
+}
 
-    pub fn toggle(&mut self) {
-        if let Some(comp) = self.components.get_mut(self.selected) {
-            comp.checked = !comp.checked;
-        }
-    }
+impl ManifestFormat {
+    pub const ALL: [Self; 3] = [Self::Json, Self::Toml, Self::Yaml];
 
-    pub fn next(&mut self) {
-        self.selected = (self.selected + 1) % self.components.len();
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Toml => "toml",
+            Self::Yaml => "yaml",
+        }
     }
 
-    pub fn prev(&mut self) {
-        self.selected = if self.selected == 0 {
-            self.components.len() - 1
-        } else {
-            self.selected - 1
-        };
+    pub fn file_name(self) -> String {
+        format!("manifest.{}", self.name())
     }
 
-    pub fn checked_components(&self) -> Vec<&ThemeComponent> {
-        self.components.iter().filter(|c| c.checked).collect()
+    pub fn serialize(self, manifest: &Manifest) -> Result<String> {
+        Ok(match self {
+            Self::Json => serde_json::to_string_pretty(manifest)?,
+            Self::Toml => toml::to_string_pretty(manifest)?,
+            Self::Yaml => serde_norway::to_string(manifest)?,
+        })
     }
 }
 
-fn draw_ui(f: &mut Frame, app: &App) {
-    let chunks = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([
-            Constraint::Length(3),
-            Constraint::Min(0),
-            Constraint::Length(3),
-        ])
-        .split(f.area());
+/// One entry of an exported component folder and where it goes back to.
+#[derive(Debug, Clone)]
+pub struct RestoreAction {
+    pub component: String,
+    pub source: std::path::PathBuf,
+    pub destination: std::path::PathBuf,
+}
 
-    // Title
-    let title = Paragraph::new("Theme Creator")
-        .style(Style::default().add_modifier(Modifier::BOLD))
-        .block(Block::default().borders(Borders::ALL));
-    f.render_widget(title, chunks[0]);
+/// Machine-readable counterpart of `theme_info.txt`, written as
+/// `manifest.json` (or TOML or YAML with `--output-format`).
+#[derive(Debug, Serialize)]
+pub struct Manifest {
+    pub theme_name: String,
+    pub created: String,
+    /// "dark" or "light", when GTK or the application style is exported.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color_scheme: Option<&'static str>,
+    pub components: Vec<ManifestComponent>,
+}
 
-    // Main content
-    match app.mode {
-        Mode::Selecting => draw_selection(f, app, chunks[1]),
-        Mode::Naming => draw_naming(f, app, chunks[1]),
-        Mode::DirectorySelection => draw_directory_selection(f, app, chunks[1]),
-        Mode::Summary => draw_summary(f, app, chunks[1]),
-        Mode::PermissionCheck => draw_permission_check(f, app, chunks[1]),
-    }
+#[derive(Debug, Serialize)]
+pub struct ManifestComponent {
+    pub name: String,
+    pub description: String,
+    pub source_paths: Vec<String>,
+    pub files: Vec<ManifestFile>,
+    /// Folders a Plasma wallpaper slideshow rotates through.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub slideshow: Vec<String>,
+}
 
-    // Status
-    let status_text = match app.mode {
-        Mode::Selecting => app.message.clone(),
-        Mode::Naming => format!("Name: {}_", app.theme_name),
-        Mode::DirectorySelection => format!(
-            "Path: {} | Enter: accept, Esc: cancel, Tab: create new",
-            app.theme_directory
-        ),
-        Mode::Summary => "Enter to create, Esc to cancel".to_string(),
-        Mode::PermissionCheck => {
-            "1: Re-run with sudo, 2: Copy chmod commands, Esc: Cancel".to_string()
-        }
-    };
+#[derive(Debug, Serialize)]
+pub struct ManifestFile {
+    pub path: String,
+    pub status: CopyStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
 
-    let status = Paragraph::new(status_text)
-        .style(Style::default().add_modifier(Modifier::REVERSED))
-        .block(Block::default().borders(Borders::ALL));
-    f.render_widget(status, chunks[2]);
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CopyStatus {
+    Copied,
+    Linked,
+    Skipped,
 }
 
-fn draw_selection(f: &mut Frame, app: &App, area: Rect) {
-    let items: Vec<ListItem> = app
-        .components
-        .iter()
-        .enumerate()
-        .map(|(i, comp)| {
-            let checkbox = if comp.checked { "[x]" } else { "[ ]" };
-            let style = if i == app.selected {
-                Style::default().add_modifier(Modifier::REVERSED | Modifier::BOLD)
-            } else {
-                Style::default()
-            };
+impl ManifestFile {
+    fn copied(path: &Path) -> Self {
+        Self {
+            path: path.display().to_string(),
+            status: CopyStatus::Copied,
+            reason: None,
+        }
+    }
 
-            let mut content = vec![
-                Line::from(vec![
-                    Span::styled(format!(" {} ", checkbox), Style::default()),
-                    Span::styled(&comp.name, style),
-                ]),
-                Line::from(vec![
-                    Span::styled("     ", Style::default()),
-                    Span::styled(&comp.description, Style::default().fg(Color::DarkGray)),
-                ]),
-            ];
-
-            // Add current style info if available
-            if let Some(ref current_style) = comp.current_style {
-                content.push(Line::from(vec![
-                    Span::styled("     ", Style::default()),
-                    Span::styled("→ ", Style::default().fg(Color::Green)),
-                    Span::styled(current_style, Style::default().fg(Color::Cyan)),
-                ]));
-            } else {
-                content.push(Line::from(vec![
-                    Span::styled("     ", Style::default()),
-                    Span::styled("→ (none detected)", Style::default().fg(Color::DarkGray)),
-                ]));
-            }
+    fn linked(path: &Path) -> Self {
+        Self {
+            path: path.display().to_string(),
+            status: CopyStatus::Linked,
+            reason: None,
+        }
+    }
 
-            ListItem::new(content)
-        })
-        .collect();
+    fn skipped(path: &Path, reason: impl ToString) -> Self {
+        Self {
+            path: path.display().to_string(),
+            status: CopyStatus::Skipped,
+            reason: Some(reason.to_string()),
+        }
+    }
+}
 
-    let mut state = ListState::default();
-    state.select(Some(app.selected));
+/// How a file differs between a previous export and the current sources.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Change {
+    Added,
+    Removed,
+    Modified,
+}
 
-    let list = List::new(items)
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .title("Select Components"),
-        )
-        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+/// A file that changed since a previous export, relative to its folder.
+#[derive(Debug)]
+pub struct DiffEntry {
+    pub change: Change,
+    pub path: std::path::PathBuf,
+}
 
-    f.render_stateful_widget(list, area, &mut state);
+/// What `create_theme` copied and skipped, shown on the results screen.
+#[derive(Debug, Default)]
+pub struct ExportResult {
+    pub theme_dir: std::path::PathBuf,
+    /// "Component: path" for every source copied or linked.
+    pub copied: Vec<String>,
+    /// "Component: path (reason)" for everything left out.
+    pub skipped: Vec<String>,
+    /// Copies that differ from their source, when `--verify` was on.
+    pub verify_failures: Vec<String>,
 }
 
-fn draw_naming(f: &mut Frame, app: &App, area: Rect) {
-    let text = vec![
-        Line::from("Enter theme name:"),
-        Line::from(""),
-        Line::from(vec![
-            Span::styled("> ", Style::default().fg(Color::Green)),
-            Span::styled(&app.theme_name, Style::default()),
-            Span::styled("_", Style::default().fg(Color::Green)),
-        ]),
-    ];
+/// A source that `create_theme` would copy, as found by `dry_run`.
+#[derive(Debug)]
+pub struct DryRunEntry {
+    pub component: String,
+    pub path: std::path::PathBuf,
+    /// File count and total bytes, or `None` when the path doesn't exist.
+    pub size: Option<(u64, u64)>,
+}
 
-    let paragraph =
-        Paragraph::new(text).block(Block::default().borders(Borders::ALL).title("Name Theme"));
-    f.render_widget(paragraph, area);
+/// One path an export copies and where it goes, as planned by
+/// `ThemeComponent::export_plan`.
+#[derive(Debug)]
+struct ExportSource {
+    /// The expanded path to copy.
+    path: std::path::PathBuf,
+    /// Where `path` is copied to, relative to the theme folder.
+    destination: std::path::PathBuf,
+    /// The entries below `path` to copy, or `None` for all of it.
+    entries: Option<Vec<std::path::PathBuf>>,
+    /// The source path as configured, or `None` for a referenced file.
+    configured: Option<String>,
 }
 
-fn draw_directory_selection(f: &mut Frame, app: &App, area: Rect) {
-    let mut lines = vec![
-        Line::from("Choose where to save your theme:"),
-        Line::from(""),
-        Line::from(vec![
-            Span::styled("Current: ", Style::default().fg(Color::Yellow)),
-            Span::styled(&app.theme_directory, Style::default().fg(Color::Cyan)),
-        ]),
-        Line::from(""),
-    ];
+impl ExportSource {
+    /// Copies `path` to `destination` below `theme_dir`, limited to
+    /// `entries` when there is a selection.
+    fn copy(
+        &self,
+        theme_dir: &Path,
+        exclude: &GlobSet,
+        on_file: &mut dyn FnMut(&Path, &Path) -> Result<()>,
+    ) -> Result<Vec<SkippedEntry>> {
+        let destination = theme_dir.join(&self.destination);
+        let Some(entries) = &self.entries else {
+            return copy_as(&self.path, &destination, exclude, on_file);
+        };
+        let mut skipped = Vec::new();
+        for entry in entries {
+            let relative = entry.strip_prefix(&self.path)?;
+            match copy_as(entry, &destination.join(relative), exclude, on_file) {
+                Ok(entry_skipped) => skipped.extend(entry_skipped),
+                Err(e) if e.is::<Cancelled>() => return Err(e),
+                Err(e) => skipped.push((entry.clone(), e)),
+            }
+        }
+        Ok(skipped)
+    }
+
+    /// Runs `copy`, then offers to retry each entry that failed through
+    /// `retry_failed`, and returns the entries left skipped.
+    fn copy_with_retry(
+        &self,
+        theme_dir: &Path,
+        exclude: &GlobSet,
+        reporter: &mut dyn Reporter,
+        ask: &mut bool,
+        sudo_unreadable: bool,
+        on_file: &mut dyn FnMut(&Path, &Path, &mut dyn Reporter) -> Result<()>,
+    ) -> Result<Vec<SkippedEntry>> {
+        let skipped = self.copy(theme_dir, exclude, &mut |from, to| {
+            on_file(from, to, &mut *reporter)
+        })?;
+        let destination = theme_dir.join(&self.destination);
+        retry_failed(
+            skipped,
+            reporter,
+            ask,
+            sudo_unreadable,
+            |entry, reporter| {
+                let entry_destination = copy_destination(&self.path, entry, &destination);
+                copy_as(entry, &entry_destination, exclude, &mut |from, to| {
+                    on_file(from, to, &mut *reporter)
+                })
+            },
+        )
+    }
+
+    /// Files and bytes `copy` would copy.
+    fn scan(&self, exclude: &GlobSet) -> (u64, u64) {
+        match &self.entries {
+            Some(entries) => entries
+                .iter()
+                .map(|entry| scan_files(entry, exclude))
+                .fold((0, 0), |(files, bytes), (f, b)| (files + f, bytes + b)),
+            None => scan_files(&self.path, exclude),
+        }
+    }
+
+    /// Adds the files `copy` would write to `files`, keyed by their path
+    /// inside the theme folder.
+    fn collect_files(
+        &self,
+        exclude: &GlobSet,
+        files: &mut std::collections::BTreeMap<std::path::PathBuf, std::path::PathBuf>,
+    ) {
+        match &self.entries {
+            Some(entries) => {
+                for entry in entries {
+                    if let Ok(relative) = entry.strip_prefix(&self.path) {
+                        let export = self.destination.join(relative);
+                        collect_files(entry, export, exclude, true, files);
+                    }
+                }
+            }
+            None => collect_files(&self.path, self.destination.clone(), exclude, true, files),
+        }
+    }
+}
+
+/// Progress of a running copy, rendered as gauges in the TUI.
+#[derive(Debug, Default, Clone)]
+pub struct CopyProgress {
+    pub component: String,
+    pub component_done: u64,
+    pub component_total: u64,
+    pub overall_done: u64,
+    pub overall_total: u64,
+}
+
+impl CopyProgress {
+    fn start_component(&mut self, component: &str, total: u64) {
+        self.component = component.to_string();
+        self.component_done = 0;
+        self.component_total = total;
+    }
 
-    if app.directory_entries.is_empty() {
-        lines.push(Line::from("Loading directory contents..."));
+    fn advance(&mut self) {
+        self.component_done += 1;
+        self.overall_done += 1;
+    }
+
+    fn component_ratio(&self) -> f64 {
+        ratio(self.component_done, self.component_total)
+    }
+
+    fn overall_ratio(&self) -> f64 {
+        ratio(self.overall_done, self.overall_total)
+    }
+}
+
+fn ratio(done: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
     } else {
-        lines.push(Line::from("Directories:"));
+        (done as f64 / total as f64).min(1.0)
+    }
+}
 
-        for (i, entry) in app.directory_entries.iter().enumerate() {
-            let style = if i == app.directory_selected {
-                Style::default().add_modifier(Modifier::REVERSED | Modifier::BOLD)
-            } else {
-                Style::default()
-            };
+/// Receives the output of long-running operations such as `create_theme`.
+/// Headless mode prints it; the TUI renders it with progress gauges.
+pub trait Reporter {
+    fn log(&mut self, line: &str);
+    fn progress(&mut self, progress: &CopyProgress);
 
-            let prefix = if entry.ends_with('/') {
-                "📁 "
-            } else {
-                "📄 "
-            };
+    /// Whether the user asked to stop; checked after every copied file.
+    fn cancelled(&mut self) -> bool {
+        false
+    }
 
-            lines.push(Line::from(vec![
-                Span::styled("  ", Style::default()),
-                Span::styled(prefix, Style::default()),
-                Span::styled(entry, style),
-            ]));
+    /// Runs a command that talks to the user on the terminal, like a sudo
+    /// password prompt.
+    fn run_interactive(&mut self, command: &mut Command) -> io::Result<std::process::ExitStatus> {
+        command.status()
+    }
+
+    /// What to do about an entry that couldn't be copied. Without anyone to
+    /// ask it is skipped, as before prompts existed.
+    fn copy_failed(&mut self, _path: &Path, _error: &anyhow::Error) -> FailureAction {
+        FailureAction::Skip
+    }
+}
+
+/// Answer to a failed copy during an export.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FailureAction {
+    Retry,
+    Skip,
+    /// Skip this and every later failure without asking.
+    SkipAll,
+    /// Stop the export like Esc does.
+    Abort,
+}
+
+/// Prints to stdout; `quiet` (`--quiet`) drops the running commentary so
+/// scripts only see errors and the result.
+struct StdoutReporter {
+    quiet: bool,
+}
+
+impl Reporter for StdoutReporter {
+    fn log(&mut self, line: &str) {
+        if !self.quiet {
+            println!("{}", line);
         }
+    }
 
-        lines.push(Line::from(""));
-        lines.push(Line::from(
-            "↑↓: Navigate | Enter: Select | Tab: Create new directory",
-        ));
+    fn progress(&mut self, _progress: &CopyProgress) {}
+}
+
+/// Keeps the TUI alive while copying. Redraws are throttled because
+/// `progress` is called once per copied file.
+struct TuiReporter<'a> {
+    terminal: &'a mut Terminal<CrosstermBackend<io::Stdout>>,
+    lines: Vec<String>,
+    progress: CopyProgress,
+    last_draw: Option<std::time::Instant>,
+    /// Esc stops the operation; only set for exports.
+    cancellable: bool,
+    cancelled: bool,
+    /// The failed entry and its error while waiting for an answer.
+    failure: Option<(String, String)>,
+}
+
+impl<'a> TuiReporter<'a> {
+    fn new(terminal: &'a mut Terminal<CrosstermBackend<io::Stdout>>) -> Self {
+        Self {
+            terminal,
+            lines: Vec::new(),
+            progress: CopyProgress::default(),
+            last_draw: None,
+            cancellable: false,
+            cancelled: false,
+            failure: None,
+        }
     }
 
-    let paragraph = Paragraph::new(lines)
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .title("Select Directory"),
-        )
-        .wrap(Wrap { trim: true });
-    f.render_widget(paragraph, area);
+    fn cancellable(mut self) -> Self {
+        self.cancellable = true;
+        self
+    }
+
+    fn redraw(&mut self, force: bool) {
+        let due = self
+            .last_draw
+            .is_none_or(|last| last.elapsed() >= std::time::Duration::from_millis(50));
+        if force || due {
+            let (progress, lines, cancellable) = (&self.progress, &self.lines, self.cancellable);
+            let failure = self.failure.as_ref();
+            let _ = self.terminal.draw(|f| {
+                draw_progress(f, progress, lines, cancellable);
+                if let Some((path, error)) = failure {
+                    draw_copy_failure(f, path, error);
+                }
+            });
+            self.last_draw = Some(std::time::Instant::now());
+        }
+    }
+
+    fn into_lines(self) -> Vec<String> {
+        self.lines
+    }
 }
 
-fn draw_summary(f: &mut Frame, app: &App, area: Rect) {
-    let checked = app.checked_components();
+impl Reporter for TuiReporter<'_> {
+    fn log(&mut self, line: &str) {
+        self.lines.push(line.to_string());
+        self.redraw(true);
+    }
 
-    let mut lines = vec![
-        Line::from(vec![
-            Span::styled("Theme: ", Style::default().bold()),
-            Span::styled(&app.theme_name, Style::default().fg(Color::Cyan)),
-        ]),
-        Line::from(""),
-    ];
+    fn progress(&mut self, progress: &CopyProgress) {
+        self.progress = progress.clone();
+        self.redraw(false);
+    }
 
-    if checked.is_empty() {
-        lines.push(Line::from("No components selected!"));
-    } else {
-        lines.push(Line::from("Components to include:"));
-        for comp in checked {
-            lines.push(Line::from(vec![
-                Span::styled("✓ ", Style::default().fg(Color::Green)),
-                Span::styled(&comp.name, Style::default().bold()),
-            ]));
-            lines.push(Line::from(vec![
-                Span::styled("  ", Style::default()),
-                Span::styled(&comp.description, Style::default().fg(Color::DarkGray)),
-            ]));
+    fn run_interactive(&mut self, command: &mut Command) -> io::Result<std::process::ExitStatus> {
+        let _ = suspend_tui(self.terminal);
+        let status = command.status();
+        let _ = resume_tui(self.terminal);
+        self.redraw(true);
+        status
+    }
+
+    fn cancelled(&mut self) -> bool {
+        while self.cancellable && !self.cancelled {
+            match event::poll(std::time::Duration::ZERO) {
+                Ok(true) => {}
+                _ => break,
+            }
+            match event::read() {
+                Ok(Event::Key(key)) => {
+                    self.cancelled = key.kind == KeyEventKind::Press && key.code == KeyCode::Esc;
+                }
+                Ok(Event::Resize(width, height)) => {
+                    let _ = resize_terminal(self.terminal, width, height);
+                    self.redraw(true);
+                }
+                _ => {}
+            }
         }
+        self.cancelled
     }
 
-    let paragraph = Paragraph::new(lines)
-        .block(Block::default().borders(Borders::ALL).title("Summary"))
-        .wrap(Wrap { trim: true });
-    f.render_widget(paragraph, area);
+    fn copy_failed(&mut self, path: &Path, error: &anyhow::Error) -> FailureAction {
+        self.failure = Some((path.display().to_string(), format!("{:#}", error)));
+        self.redraw(true);
+        let action = loop {
+            match event::read() {
+                Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => match key.code {
+                    KeyCode::Char('r') => break FailureAction::Retry,
+                    KeyCode::Char('s') => break FailureAction::Skip,
+                    KeyCode::Char('a') => break FailureAction::SkipAll,
+                    KeyCode::Esc => break FailureAction::Abort,
+                    _ => {}
+                },
+                Ok(Event::Resize(width, height)) => {
+                    let _ = resize_terminal(self.terminal, width, height);
+                    self.redraw(true);
+                }
+                Ok(_) => {}
+                Err(_) => break FailureAction::Skip,
+            }
+        };
+        self.failure = None;
+        self.redraw(true);
+        action
+    }
 }
 
-fn draw_permission_check(f: &mut Frame, app: &App, area: Rect) {
-    let mut lines = vec![
-        Line::from(vec![Span::styled(
-            "Permission Issues Found",
-            Style::default().fg(Color::Red).bold(),
-        )]),
-        Line::from(""),
-    ];
+#[derive(Debug)]
+pub struct PermissionIssue {
+    pub component: String,
+    pub path: String,
+    pub issue_type: PermissionIssueType,
+}
 
-    if app.permission_issues.is_empty() {
-        lines.push(Line::from("No permission issues detected!"));
-    } else {
-        lines.push(Line::from(
-            "The following components have permission issues:",
-        ));
-        lines.push(Line::from(""));
+#[derive(Debug)]
+pub enum PermissionIssueType {
+    NoReadAccess,
+    NoWriteAccess,
+    SudoRequired,
+}
 
-        for (i, issue) in app.permission_issues.iter().enumerate() {
-            let issue_text = match issue.issue_type {
-                PermissionIssueType::NoReadAccess => "No read access",
-                PermissionIssueType::NoWriteAccess => "No write access",
-                PermissionIssueType::SudoRequired => "Sudo required",
-            };
+impl PermissionIssueType {
+    pub fn description(&self) -> &'static str {
+        match self {
+            PermissionIssueType::NoReadAccess => "No read access",
+            PermissionIssueType::NoWriteAccess => "No write access",
+            PermissionIssueType::SudoRequired => "Sudo required",
+        }
+    }
+}
 
-            lines.push(Line::from(vec![
-                Span::styled(format!("{}.", i + 1), Style::default().fg(Color::Yellow)),
-                Span::styled(" ", Style::default()),
-                Span::styled(&issue.component, Style::default().bold()),
-                Span::styled(
-                    format!(" ({})", issue_text),
-                    Style::default().fg(Color::Red),
-                ),
-            ]));
-            lines.push(Line::from(vec![
-                Span::styled("   Path: ", Style::default()),
-                Span::styled(&issue.path, Style::default().fg(Color::Blue)),
-            ]));
-            lines.push(Line::from(""));
+/// User configuration read from `~/.config/kde-copycat/config.toml`.
+///
+/// ```toml
+/// [[components]]
+/// name = "GTK Themes"            # existing component: add paths
+/// extra_paths = ["/run/current-system/sw/share/themes/"]
+///
+/// [[components]]
+/// name = "Dunst"                 # unknown name: new component
+/// source_paths = ["~/.config/dunst/"]
+/// description = "Notification daemon theme"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    pub components: Vec<ComponentConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ComponentConfig {
+    pub name: String,
+    pub description: Option<String>,
+    /// Replaces the built-in source paths.
+    pub source_paths: Option<Vec<String>>,
+    /// Appended to the built-in (or replaced) source paths.
+    #[serde(default)]
+    pub extra_paths: Vec<String>,
+    /// Replaces the default exclude patterns.
+    pub exclude: Option<Vec<String>>,
+    /// Whether Icons and Cursors also export inherited themes.
+    pub follow_inherits: Option<bool>,
+    /// Group the component is listed under in the TUI.
+    pub category: Option<String>,
+}
+
+impl Config {
+    pub fn path() -> std::path::PathBuf {
+        expand_tilde("~/.config/kde-copycat/config.toml")
+    }
+
+    /// Loads the config file, returning the defaults when it doesn't exist.
+    pub fn load() -> Result<Self> {
+        let path = Self::path();
+        if !path.exists() {
+            return Ok(Self::default());
         }
 
-        lines.push(Line::from(vec![Span::styled(
-            "Options:",
-            Style::default().bold(),
-        )]));
-        lines.push(Line::from("1. Re-run with sudo privileges"));
-        lines.push(Line::from("2. Copy chmod commands to clipboard"));
-        lines.push(Line::from("Esc. Cancel and go back"));
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let config: Self = toml::from_str(&content)
+            .with_context(|| format!("Invalid config {}", path.display()))?;
+        for entry in &config.components {
+            for pattern in entry.exclude.iter().flatten() {
+                Glob::new(pattern).with_context(|| {
+                    format!(
+                        "Invalid exclude pattern for {} in {}",
+                        entry.name,
+                        path.display()
+                    )
+                })?;
+            }
+        }
+        Ok(config)
     }
 
-    let paragraph = Paragraph::new(lines)
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .title("Permission Check"),
-        )
-        .wrap(Wrap { trim: true });
-    f.render_widget(paragraph, area);
+    /// Overrides or extends `components` with the configured entries.
+    pub fn apply(&self, components: &mut Vec<ThemeComponent>) {
+        for entry in &self.components {
+            if let Some(comp) = components
+                .iter_mut()
+                .find(|c| c.name.eq_ignore_ascii_case(&entry.name))
+            {
+                if let Some(paths) = &entry.source_paths {
+                    comp.source_paths = paths.clone();
+                }
+                comp.source_paths.extend(entry.extra_paths.iter().cloned());
+                if let Some(description) = &entry.description {
+                    comp.description = description.clone();
+                }
+                if let Some(exclude) = &entry.exclude {
+                    comp.exclude = exclude.clone();
+                }
+                if let Some(follow_inherits) = entry.follow_inherits {
+                    comp.follow_inherits = follow_inherits;
+                }
+                if let Some(category) = &entry.category {
+                    comp.category = category.clone();
+                }
+            } else {
+                let paths: Vec<&str> = entry
+                    .source_paths
+                    .iter()
+                    .flatten()
+                    .chain(&entry.extra_paths)
+                    .map(String::as_str)
+                    .collect();
+                let mut comp = ThemeComponent::new(
+                    &entry.name,
+                    paths,
+                    entry.description.as_deref().unwrap_or("Custom component"),
+                );
+                if let Some(exclude) = &entry.exclude {
+                    comp.exclude = exclude.clone();
+                }
+                if let Some(category) = &entry.category {
+                    comp.category = category.clone();
+                }
+                components.push(comp);
+            }
+        }
+    }
 }
 
-fn main() -> Result<()> {
-    let mut app = App::new();
+/// What is remembered between runs, kept next to the log in
+/// `~/.cache/kde-copycat/state.toml`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct State {
+    /// The output folder last accepted in the TUI.
+    pub theme_directory: Option<String>,
+}
 
-    // Initialize terminal with error handling
-    let result = (|| -> Result<()> {
-        enable_raw_mode()?;
-        let mut stdout = io::stdout();
-        execute!(stdout, EnterAlternateScreen)?;
-        let backend = CrosstermBackend::new(stdout);
-        let mut terminal = Terminal::new(backend)?;
+impl State {
+    pub fn path() -> std::path::PathBuf {
+        log_path().with_file_name("state.toml")
+    }
 
-        // Run the main app loop
-        run_app_loop(&mut terminal, &mut app)?;
+    /// Loads the saved state; a missing or unreadable file is a fresh start.
+    pub fn load() -> Self {
+        let path = Self::path();
+        let Ok(content) = fs::read_to_string(&path) else {
+            return Self::default();
+        };
+        toml::from_str(&content).unwrap_or_else(|e| {
+            log::warn!("Ignoring invalid {}: {}", path.display(), e);
+            Self::default()
+        })
+    }
 
-        // Cleanup
-        disable_raw_mode()?;
-        execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
-        terminal.show_cursor()?;
-        Ok(())
-    })();
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, toml::to_string(self)?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
 
-    if let Err(e) = result {
-        eprintln!(
-            "Terminal error: {}. Make sure you're running this in a proper terminal.",
-            e
+/// The selection in progress, saved to `~/.cache/kde-copycat/session.toml` on
+/// every change so a crashed terminal doesn't lose it. Offered back on the
+/// next launch and removed whenever the TUI exits normally.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    #[serde(default)]
+    pub theme_name: String,
+    /// Names of the checked components.
+    #[serde(default)]
+    pub checked: Vec<String>,
+}
+
+impl Session {
+    pub fn path() -> std::path::PathBuf {
+        log_path().with_file_name("session.toml")
+    }
+
+    /// The selection and name currently in the TUI.
+    pub fn of(app: &App) -> Self {
+        Self {
+            theme_name: app.theme_name.clone(),
+            checked: app
+                .checked_components()
+                .iter()
+                .map(|c| c.name.clone())
+                .collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.theme_name.is_empty() && self.checked.is_empty()
+    }
+
+    /// The saved session, unless there is none worth offering.
+    pub fn load() -> Option<Self> {
+        let path = Self::path();
+        let content = fs::read_to_string(&path).ok()?;
+        match toml::from_str::<Self>(&content) {
+            Ok(session) => Some(session).filter(|session| !session.is_empty()),
+            Err(e) => {
+                log::warn!("Ignoring invalid {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    /// Writes the session, or removes the file when there is nothing to keep.
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path();
+        if self.is_empty() {
+            return Self::clear();
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, toml::to_string(self)?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    pub fn clear() -> Result<()> {
+        match fs::remove_file(Self::path()) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the saved components and restores the name.
+    pub fn apply(&self, app: &mut App) {
+        for comp in &mut app.components {
+            comp.checked = self.checked.contains(&comp.name);
+        }
+        app.theme_name = self.theme_name.clone();
+        app.message = format!(
+            "Restored the last session: {} of {} components selected",
+            app.checked_components().len(),
+            app.components.len()
         );
-        return Err(e);
     }
+}
 
-    Ok(())
+/// Named component selections, kept in `~/.config/kde-copycat/profiles.toml`
+/// and applied with `p` on the component list.
+///
+/// ```toml
+/// [[profiles]]
+/// name = "Just GTK"
+/// components = ["GTK Themes", "Icons"]
+/// ```
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profiles {
+    #[serde(default)]
+    pub profiles: Vec<Profile>,
 }
 
-fn run_app_loop(
-    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
-    app: &mut App,
-) -> Result<()> {
-    loop {
-        terminal.draw(|f| draw_ui(f, &app))?;
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    pub name: String,
+    /// Component names or short names, as in a recipe.
+    pub components: Vec<String>,
+}
 
-        if event::poll(std::time::Duration::from_millis(100))
-            .context("Failed to poll for events")?
-        {
-            if let Event::Key(key) = event::read()? {
-                if key.kind == KeyEventKind::Press {
-                    match app.mode {
-                        Mode::Selecting => match key.code {
-                            KeyCode::Char('q') | KeyCode::Esc => break,
-                            KeyCode::Up | KeyCode::Left => app.prev(),
+impl Profiles {
+    pub fn path() -> std::path::PathBuf {
+        expand_tilde("~/.config/kde-copycat/profiles.toml")
+    }
+
+    /// Loads the saved profiles, none when the file doesn't exist.
+    pub fn load() -> Result<Self> {
+        let path = Self::path();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        toml::from_str(&content).with_context(|| format!("Invalid profiles {}", path.display()))
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, toml::to_string(self)?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+/// `name` in lowercase with words joined by `-`, as used on the command line.
+fn short_name_of(name: &str) -> String {
+    name.to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// A theme recipe for `--from`: everything needed to export a theme without
+/// the TUI. Components are given by name or short name.
+///
+/// ```toml
+/// name = "Nordic"
+/// out = "~/CustomThemes"        # optional
+/// archive = true                # optional
+/// components = ["GTK Themes", "icons", "cursors"]
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Recipe {
+    pub name: String,
+    pub out: Option<String>,
+    #[serde(default)]
+    pub archive: bool,
+    pub components: Vec<String>,
+}
+
+impl Recipe {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let recipe: Self = toml::from_str(&content)
+            .with_context(|| format!("Invalid theme recipe {}", path.display()))?;
+        if recipe.components.is_empty() {
+            anyhow::bail!("{} lists no components", path.display());
+        }
+        Ok(recipe)
+    }
+
+    /// Fills in the options not given on the command line, after checking
+    /// every component exists. Command-line options take precedence.
+    pub fn apply(&self, path: &Path, app: &App, cli: &mut CliArgs) -> Result<()> {
+        let mut components = Vec::new();
+        for name in &self.components {
+            if cli.add_components.iter().any(|(custom, _)| custom == name) {
+                components.push(name.clone());
+                continue;
+            }
+            let index = app
+                .find_component(name)
+                .with_context(|| format!("In {}", path.display()))?;
+            components.push(app.components[index].short_name());
+        }
+
+        cli.name.get_or_insert_with(|| self.name.clone());
+        if cli.out.is_none() {
+            cli.out = self.out.clone();
+        }
+        if cli.components.is_empty() {
+            cli.components = components;
+        }
+        cli.archive |= self.archive;
+        Ok(())
+    }
+}
+
+impl Default for App {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl App {
+    pub fn new() -> Self {
+        let mut components = vec![
+            ThemeComponent::new(
+                "Global Theme",
+                vec![
+                    "~/.local/share/plasma/look-and-feel/",
+                    "/usr/share/plasma/look-and-feel/",
+                ],
+                "Plasma Look and Feel packages",
+            ),
+            ThemeComponent::new(
+                "GTK Themes",
+                vec![
+                    "~/.themes/",
+                    "~/.local/share/themes/",
+                    "/usr/share/themes/",
+                    "~/.config/gtk-3.0/gtk.css",
+                    "~/.config/gtk-4.0/",
+                ],
+                "GTK2/GTK3/GTK4 theme files, custom CSS and libadwaita overrides",
+            ),
+            ThemeComponent::new(
+                "Icons",
+                vec!["~/.icons/", "~/.local/share/icons/", "/usr/share/icons/"],
+                "Icon themes",
+            ),
+            ThemeComponent::new(
+                "Cursors",
+                vec!["~/.icons/", "~/.local/share/icons/", "/usr/share/icons/"],
+                "Mouse cursor themes",
+            ),
+            ThemeComponent::new(
+                "Fonts",
+                vec![
+                    "~/.local/share/fonts/",
+                    "~/.fonts/",
+                    "~/.config/fontconfig/",
+                ],
+                "User fonts, fontconfig and the files of the configured fonts",
+            ),
+            ThemeComponent::new("Qt/KDE Styles", vec!["~/.config/"], "Qt5/Qt6 styles"),
+            ThemeComponent::new(
+                "Application Style",
+                vec!["~/.config/", "/etc/xdg/"],
+                "Current desktop application style (Oxygen, Edge Runner, etc.)",
+            ),
+            ThemeComponent::new(
+                "Colors Schemes",
+                vec!["~/.local/share/color-schemes/"],
+                "KDE color schemes",
+            ),
+            ThemeComponent::new(
+                "Window Decorations",
+                vec![
+                    "~/.config/kwinrc",
+                    "~/.config/awesome/",
+                    "~/.config/i3/",
+                    "~/.config/openbox/",
+                    "~/.config/bspwm/",
+                    "/usr/share/kde4/config/",
+                ],
+                "Window manager decorations and borders",
+            ),
+            ThemeComponent::new(
+                "KWin Scripts",
+                vec!["~/.local/share/kwin/scripts/", "~/.config/kwinrulesrc"],
+                "KWin scripts and window rules",
+            ),
+            ThemeComponent::new(
+                "Desktop Effects",
+                vec!["~/.local/share/kwin/effects/"],
+                "Third-party KWin effects (kwinrc goes with Window Decorations)",
+            ),
+            ThemeComponent::new(
+                "Window Manager Themes",
+                vec!["~/.config/hypr/", "~/.config/sway/"],
+                "Wayland compositor configurations (Hyprland, Sway)",
+            ),
+            ThemeComponent::new(
+                "Splash Screen",
+                vec![
+                    "/usr/share/plymouth/themes/",
+                    "/boot/grub/themes/",
+                    "/etc/alternatives/",
+                    "~/.config/plymouth/",
+                    "~/.local/share/plasma/look-and-feel/",
+                    "~/.config/ksplashrc",
+                ],
+                "Boot splash (Plymouth, GRUB) and the Plasma splash screen",
+            ),
+            ThemeComponent::new(
+                "SDDM Theme",
+                vec!["/usr/share/sddm/themes/"],
+                "SDDM login manager theme",
+            ),
+            ThemeComponent::new(
+                "Terminal Themes",
+                vec!["~/.config/alacritty/", "~/.config/kitty/"],
+                "Terminal themes",
+            ),
+            ThemeComponent::new(
+                "Prompt Theme",
+                vec!["~/.config/starship.toml", "~/.p10k.zsh"],
+                "Shell prompt themes (Starship, Powerlevel10k)",
+            ),
+            ThemeComponent::new(
+                "Konsole Profiles",
+                vec!["~/.local/share/konsole/", "~/.config/konsolerc"],
+                "Konsole terminal profiles and color schemes",
+            ),
+            ThemeComponent::new(
+                "Plasma Widgets",
+                vec![
+                    "~/.local/share/plasma/plasmoids/",
+                    "/usr/share/plasma/plasmoids/",
+                ],
+                "Plasma desktop widgets (plasmoids)",
+            ),
+            ThemeComponent::new(
+                "Panel Layout",
+                vec![
+                    "~/.config/plasma-org.kde.plasma.desktop-appletsrc",
+                    "~/.config/plasmashellrc",
+                    "~/.config/lattedockrc",
+                    "~/.local/share/latte/",
+                ],
+                "Plasma panel and Latte Dock layouts",
+            ),
+            ThemeComponent::new(
+                "Wallpapers",
+                vec!["~/.local/share/wallpapers/", "/usr/share/wallpapers/"],
+                "Desktop wallpapers, including the active Plasma wallpaper",
+            ),
+            ThemeComponent::new(
+                "Launcher Themes",
+                vec!["~/.config/rofi/", "~/.config/wofi/"],
+                "Rofi and Wofi application launcher themes",
+            ),
+            ThemeComponent::new(
+                "Notification Themes",
+                vec!["~/.config/dunst/", "~/.config/mako/"],
+                "Dunst and Mako notification styling",
+            ),
+            ThemeComponent::new(
+                "Status Bar",
+                vec!["~/.config/waybar/", "~/.config/polybar/"],
+                "Waybar and Polybar configuration and styling",
+            ),
+            ThemeComponent::new(
+                "GNOME Shell",
+                vec![
+                    "~/.local/share/gnome-shell/extensions/",
+                    "~/.themes/",
+                    "~/.local/share/themes/",
+                ],
+                "GNOME Shell extensions and the Shell part of installed themes",
+            ),
+            ThemeComponent::new(
+                "Xfce Settings",
+                vec!["~/.config/xfce4/"],
+                "Xfce appearance, window manager and panel settings (xfconf)",
+            ),
+        ];
+
+        let mut message = SELECTING_HINT.to_string();
+        match Config::load() {
+            Ok(config) => config.apply(&mut components),
+            Err(e) => {
+                log::warn!("{:#}", e);
+                message = format!("{:#} (using defaults)", e);
+            }
+        }
+
+        let default_theme_dir = if let Some(home) = own_home_dir() {
+            home.join("CustomThemes").to_string_lossy().to_string()
+        } else {
+            "./CustomThemes".to_string()
+        };
+        // The folder used last time, while it still exists
+        let default_theme_dir = State::load()
+            .theme_directory
+            .filter(|dir| Path::new(dir).is_dir())
+            .unwrap_or(default_theme_dir);
+
+        Self {
+            components,
+            selected: 0,
+            theme_name: String::new(),
+            mode: Mode::Selecting,
+            message,
+            permission_issues: Vec::new(),
+            theme_directory: default_theme_dir,
+            directory_entries: Vec::new(),
+            directory_selected: 0,
+            directory_error: None,
+            selection_offset: std::cell::Cell::new(0),
+            directory_offset: std::cell::Cell::new(0),
+            new_directory_name: String::new(),
+            new_component_name: String::new(),
+            new_component_path: String::new(),
+            path_input: String::new(),
+            pending_create_path: None,
+            create_archive: false,
+            link_sources: false,
+            verify: false,
+            dedupe: false,
+            git_add: false,
+            manifest_format: ManifestFormat::Json,
+            sudo_unreadable: false,
+            restore_entries: Vec::new(),
+            restore_selected: 0,
+            restore_plan: Vec::new(),
+            restore_skipped: Vec::new(),
+            restore_component: 0,
+            restore_offset: std::cell::Cell::new(0),
+            exit_report: Vec::new(),
+            dry_run_report: Vec::new(),
+            diff_report: Vec::new(),
+            diff_scroll: 0,
+            saved_session: None,
+            export_result: None,
+            results_scroll: 0,
+            estimated_size: 0,
+            flatpak_note: detect_flatpak_themes(),
+            filter: String::new(),
+            filtering: false,
+            show_help: false,
+            selection_history: Vec::new(),
+            source_selected: 0,
+            profiles: Vec::new(),
+            profile_selected: 0,
+            profile_name: String::new(),
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        if !self.visible_components().contains(&self.selected) {
+            return;
+        }
+        self.remember_selection();
+        if let Some(comp) = self.components.get_mut(self.selected) {
+            comp.checked = !comp.checked;
+        }
+    }
+
+    /// Saves the current selection for `undo_selection`.
+    fn remember_selection(&mut self) {
+        const MAX_UNDO: usize = 20;
+
+        let checked = self
+            .checked_components()
+            .iter()
+            .map(|c| c.name.clone())
+            .collect();
+        self.selection_history.push(checked);
+        if self.selection_history.len() > MAX_UNDO {
+            self.selection_history.remove(0);
+        }
+    }
+
+    /// Restores the selection from before the last toggle or select all/none.
+    pub fn undo_selection(&mut self) {
+        let Some(checked) = self.selection_history.pop() else {
+            self.message = "Nothing to undo".to_string();
+            return;
+        };
+        for comp in &mut self.components {
+            comp.checked = checked.contains(&comp.name);
+        }
+        self.message = format!(
+            "Undone: {} of {} components selected",
+            self.checked_components().len(),
+            self.components.len()
+        );
+    }
+
+    /// Adds the component typed in the TUI, already checked and selected.
+    pub fn add_custom_component(&mut self) -> Result<()> {
+        let path = self.new_component_path.trim();
+        if path.is_empty() {
+            anyhow::bail!("Path cannot be empty");
+        }
+        if !expand_path(path).exists() {
+            anyhow::bail!("{} does not exist", expand_path(path).display());
+        }
+
+        let mut component = ThemeComponent::new(
+            self.new_component_name.trim(),
+            vec![path],
+            "Custom component",
+        );
+        component.checked = true;
+        component.runtime = true;
+        self.components.push(component);
+
+        self.filter.clear();
+        self.selected = self.components.len() - 1;
+        self.message = format!("Added {}", self.new_component_name.trim());
+        Ok(())
+    }
+
+    /// Checks or unchecks every visible component at once.
+    pub fn set_all_checked(&mut self, checked: bool) {
+        self.remember_selection();
+        for i in self.visible_components() {
+            self.components[i].checked = checked;
+        }
+        self.message = format!(
+            "{} of {} components selected",
+            self.checked_components().len(),
+            self.components.len()
+        );
+    }
+
+    /// Checks every visible component in the selected one's category, or
+    /// unchecks them when they all are already.
+    pub fn toggle_category(&mut self) {
+        let Some(category) = self
+            .components
+            .get(self.selected)
+            .map(|c| c.category.clone())
+        else {
+            return;
+        };
+        let group: Vec<usize> = self
+            .visible_components()
+            .into_iter()
+            .filter(|&i| self.components[i].category == category)
+            .collect();
+        let checked = !group.iter().all(|&i| self.components[i].checked);
+        self.remember_selection();
+        for &i in &group {
+            self.components[i].checked = checked;
+        }
+        self.message = format!(
+            "{} {} ({} components)",
+            if checked { "Selected" } else { "Unselected" },
+            category,
+            group.len()
+        );
+    }
+
+    /// The components of `restore_plan`, in plan order.
+    pub fn restore_components(&self) -> Vec<&str> {
+        let mut components: Vec<&str> = Vec::new();
+        for action in &self.restore_plan {
+            if !components.contains(&action.component.as_str()) {
+                components.push(&action.component);
+            }
+        }
+        components
+    }
+
+    /// The planned restores of the components left ticked.
+    pub fn included_restore_plan(&self) -> Vec<RestoreAction> {
+        self.restore_plan
+            .iter()
+            .filter(|action| !self.restore_skipped.contains(&action.component))
+            .cloned()
+            .collect()
+    }
+
+    /// Checks exactly the components of `profile`, reporting the names that
+    /// no longer match a component.
+    pub fn apply_profile(&mut self, profile: &Profile) {
+        self.remember_selection();
+        self.components.iter_mut().for_each(|c| c.checked = false);
+        let mut unknown = Vec::new();
+        for name in &profile.components {
+            match self.find_component(name) {
+                Ok(index) => self.components[index].checked = true,
+                Err(_) => unknown.push(name.as_str()),
+            }
+        }
+        self.message = format!(
+            "Profile {}: {} of {} components selected",
+            profile.name,
+            self.checked_components().len(),
+            self.components.len()
+        );
+        if !unknown.is_empty() {
+            self.message
+                .push_str(&format!(" (unknown: {})", unknown.join(", ")));
+        }
+    }
+
+    /// Saves the checked components as `name`, replacing a profile of the
+    /// same name.
+    pub fn save_profile(&mut self, name: &str) -> Result<()> {
+        let profile = Profile {
+            name: name.to_string(),
+            components: self
+                .checked_components()
+                .iter()
+                .map(|c| c.name.clone())
+                .collect(),
+        };
+        match self
+            .profiles
+            .iter()
+            .position(|p| p.name.eq_ignore_ascii_case(name))
+        {
+            Some(index) => self.profiles[index] = profile,
+            None => self.profiles.push(profile),
+        }
+        Profiles {
+            profiles: self.profiles.clone(),
+        }
+        .save()
+    }
+
+    /// Moves to the next visible component whose name starts with `letter`,
+    /// wrapping around the list.
+    pub fn jump_to_letter(&mut self, letter: char) {
+        let visible = self.visible_components();
+        let start = visible
+            .iter()
+            .position(|&i| i == self.selected)
+            .map_or(0, |pos| pos + 1);
+        let found = visible
+            .iter()
+            .cycle()
+            .skip(start)
+            .take(visible.len())
+            .find(|&&i| {
+                self.components[i]
+                    .name
+                    .chars()
+                    .next()
+                    .is_some_and(|first| first.eq_ignore_ascii_case(&letter))
+            });
+        if let Some(&index) = found {
+            self.selected = index;
+        }
+    }
+
+    pub fn next(&mut self) {
+        let visible = self.visible_components();
+        if let Some(pos) = visible.iter().position(|&i| i == self.selected) {
+            self.selected = visible[(pos + 1) % visible.len()];
+        } else if let Some(&first) = visible.first() {
+            self.selected = first;
+        }
+    }
+
+    pub fn prev(&mut self) {
+        let visible = self.visible_components();
+        if let Some(pos) = visible.iter().position(|&i| i == self.selected) {
+            self.selected = visible[if pos == 0 { visible.len() - 1 } else { pos - 1 }];
+        } else if let Some(&last) = visible.last() {
+            self.selected = last;
+        }
+    }
+
+    /// Swaps the selected component with its visible neighbour, changing the
+    /// order components are exported in.
+    pub fn move_selected(&mut self, down: bool) {
+        let visible = self.visible_components();
+        let Some(pos) = visible.iter().position(|&i| i == self.selected) else {
+            return;
+        };
+        let target = if down {
+            visible.get(pos + 1)
+        } else {
+            pos.checked_sub(1).and_then(|pos| visible.get(pos))
+        };
+        // Components only move within their group
+        let category = &self.components[self.selected].category;
+        if let Some(&target) = target.filter(|&&t| self.components[t].category == *category) {
+            self.components.swap(self.selected, target);
+            self.selected = target;
+        }
+    }
+
+    /// Indices of the components matching the current filter, in list
+    /// order: by group, then in their order within `components`.
+    pub fn visible_components(&self) -> Vec<usize> {
+        let mut visible: Vec<usize> = self
+            .components
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| {
+                self.filter.is_empty()
+                    || find_ignore_case(&c.name, &self.filter).is_some()
+                    || find_ignore_case(&c.description, &self.filter).is_some()
+            })
+            .map(|(i, _)| i)
+            .collect();
+        visible.sort_by_key(|&i| self.category_rank(&self.components[i].category));
+        visible
+    }
+
+    /// Position of `category` among the groups of the list.
+    fn category_rank(&self, category: &str) -> usize {
+        CATEGORIES
+            .iter()
+            .position(|&c| c == category)
+            .unwrap_or_else(|| {
+                let first = self.components.iter().position(|c| c.category == category);
+                CATEGORIES.len() + first.unwrap_or(0)
+            })
+    }
+
+    /// Keeps `selected` on a visible component after the filter changed.
+    fn update_filter(&mut self) {
+        let visible = self.visible_components();
+        if !visible.contains(&self.selected) {
+            if let Some(&first) = visible.first() {
+                self.selected = first;
+            }
+        }
+    }
+
+    /// Whether keys are currently going into a text field, where `?` is text.
+    pub fn is_typing(&self) -> bool {
+        match self.mode {
+            Mode::Selecting => self.filtering,
+            Mode::Naming
+            | Mode::NewDirectory
+            | Mode::AddComponentName
+            | Mode::AddComponentPath
+            | Mode::EditPath
+            | Mode::ProfileName => true,
+            _ => false,
+        }
+    }
+
+    /// Inserts pasted text into the field being typed in, as one line: a
+    /// copied trailing newline must not count as Enter.
+    pub fn paste(&mut self, text: &str) {
+        let text: String = text.chars().filter(|c| !c.is_control()).collect();
+        match self.mode {
+            Mode::Selecting if self.filtering => {
+                self.filter.push_str(&text);
+                self.update_filter();
+            }
+            Mode::Naming => self.theme_name.push_str(&text),
+            Mode::NewDirectory => self.new_directory_name.push_str(&text),
+            Mode::AddComponentName => self.new_component_name.push_str(&text),
+            Mode::AddComponentPath => self.new_component_path.push_str(&text),
+            Mode::EditPath => {
+                self.path_input.push_str(&text);
+                self.pending_create_path = None;
+            }
+            Mode::ProfileName => self.profile_name.push_str(&text),
+            _ => {}
+        }
+    }
+
+    pub fn checked_components(&self) -> Vec<&ThemeComponent> {
+        self.components.iter().filter(|c| c.checked).collect()
+    }
+
+    /// What `comp` leaves out of its sources. Cursor files live inside icon
+    /// themes and Shell themes inside GTK themes; while "Cursors" or "GNOME
+    /// Shell" is checked they are exported there and not a second time.
+    pub fn exclude_set(&self, comp: &ThemeComponent) -> GlobSet {
+        let is_checked = |name: &str| self.components.iter().any(|c| c.checked && c.name == name);
+        let extra: &[&str] = match comp.name.as_str() {
+            "Icons" if is_checked("Cursors") => &["cursors"],
+            "GTK Themes" if is_checked("GNOME Shell") => &["gnome-shell"],
+            _ => &[],
+        };
+        comp.exclude_set(extra)
+    }
+
+    /// Resolves a component by its name, its short name or the first word of
+    /// it, so `GTK Themes`, `gtk-themes` and `gtk` all select "GTK Themes".
+    pub fn find_component(&self, name: &str) -> Result<usize> {
+        let short_name = short_name_of(name);
+        if let Some(index) = self
+            .components
+            .iter()
+            .position(|c| c.short_name() == short_name)
+        {
+            return Ok(index);
+        }
+
+        let matches: Vec<usize> = self
+            .components
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| c.short_name().split('-').next() == Some(short_name.as_str()))
+            .map(|(i, _)| i)
+            .collect();
+
+        match matches.as_slice() {
+            [index] => Ok(*index),
+            [] => anyhow::bail!(
+                "Unknown component: {} (see --help for the list)",
+                name.trim()
+            ),
+            _ => anyhow::bail!(
+                "Ambiguous component '{}': could be {}",
+                name.trim(),
+                matches
+                    .iter()
+                    .map(|&i| self.components[i].short_name())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct CliArgs {
+    pub name: Option<String>,
+    pub out: Option<String>,
+    pub components: Vec<String>,
+    pub yes: bool,
+    pub archive: bool,
+    pub link: bool,
+    pub verify: bool,
+    pub dedupe: bool,
+    pub git_add: bool,
+    pub dry_run: bool,
+    pub diff: Option<String>,
+    pub from: Option<String>,
+    pub home: Option<String>,
+    pub user: Option<String>,
+    pub add_components: Vec<(String, String)>,
+    pub skip_paths: Vec<String>,
+    pub output_format: ManifestFormat,
+    pub restore: Option<String>,
+    pub verbose: bool,
+    pub quiet: bool,
+    pub list_detected: bool,
+    pub ascii: bool,
+    pub help: bool,
+}
+
+impl CliArgs {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut cli = Self::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => {
+                    (flag.to_string(), Some(value.to_string()))
+                }
+                _ => (arg.clone(), None),
+            };
+            let mut value = || {
+                inline_value
+                    .clone()
+                    .or_else(|| args.next())
+                    .with_context(|| format!("Missing value for {}", flag))
+            };
+
+            match flag.as_str() {
+                "--name" | "-n" => cli.name = Some(value()?),
+                "--out" | "-o" => cli.out = Some(value()?),
+                "--components" | "-c" => cli.components.extend(
+                    value()?
+                        .split(',')
+                        .map(|c| c.trim().to_string())
+                        .filter(|c| !c.is_empty()),
+                ),
+                "--yes" | "-y" => cli.yes = true,
+                "--archive" | "-a" => cli.archive = true,
+                "--link" => cli.link = true,
+                "--verify" => cli.verify = true,
+                "--dedupe" => cli.dedupe = true,
+                "--git-add" => cli.git_add = true,
+                "--dry-run" => cli.dry_run = true,
+                "--diff" => cli.diff = Some(value()?),
+                "--from" => cli.from = Some(value()?),
+                "--home" => cli.home = Some(value()?),
+                "--user" | "-u" => cli.user = Some(value()?),
+                "--add-component" => {
+                    let spec = value()?;
+                    let (name, path) = spec
+                        .split_once('=')
+                        .with_context(|| format!("Expected NAME=PATH, got {}", spec))?;
+                    cli.add_components
+                        .push((name.trim().to_string(), path.trim().to_string()));
+                }
+                "--skip-path" => cli.skip_paths.push(value()?),
+                "--output-format" => cli.output_format = value()?.parse()?,
+                "--restore" | "-r" => cli.restore = Some(value()?),
+                "--verbose" | "-v" => cli.verbose = true,
+                "--quiet" | "-q" => cli.quiet = true,
+                "--list-detected" => cli.list_detected = true,
+                "--ascii" => cli.ascii = true,
+                "--help" | "-h" => cli.help = true,
+                _ => anyhow::bail!("Unknown argument: {} (see --help)", arg),
+            }
+        }
+
+        Ok(cli)
+    }
+
+    /// Any theme option on the command line skips the TUI entirely.
+    pub fn is_headless(&self) -> bool {
+        self.name.is_some()
+            || self.out.is_some()
+            || !self.components.is_empty()
+            || self.diff.is_some()
+    }
+}
+
+fn print_usage(app: &App) {
+    println!("Usage: kde-copycat [OPTIONS]");
+    println!();
+    println!("Without options an interactive TUI is started.");
+    println!();
+    println!("Options:");
+    println!("  -n, --name <NAME>          Theme name (required for headless mode)");
+    println!("  -o, --out <DIR>            Directory to save the theme in");
+    println!("  -c, --components <LIST>    Comma-separated components, or 'all'");
+    println!("  -a, --archive              Also write <NAME>.tar.gz next to the theme");
+    println!("      --link                 Move home files into the theme and symlink them back");
+    println!("      --verify               Check every copied file against its source");
+    println!("      --dedupe               Hardlink identical files within each component");
+    println!("      --git-add              Stage the export when it is inside a git repository");
+    println!("      --dry-run              Show what would be copied without writing");
+    println!("      --diff <DIR>           Show what changed since the export in DIR");
+    println!("      --from <FILE>          Export the theme described in a recipe file");
+    println!("      --home <DIR>           Home directory to read the theme from");
+    println!("  -u, --user <USER>          Read (or restore) another user's theme");
+    println!("      --add-component <NAME=PATH>  Add a custom component");
+    println!("      --skip-path <PATH>     Leave one of a component's source paths out");
+    println!("      --output-format <FMT>  Write the manifest as json (default), toml or yaml");
+    println!("  -r, --restore <DIR>        Restore a previously exported theme");
+    println!("  -y, --yes                  Do not ask for confirmation");
+    println!("  -v, --verbose              Print the full log to stderr");
+    println!("  -q, --quiet                Only print errors and the saved theme's path");
+    println!("      --list-detected        Print what each component detected and exit");
+    println!("      --ascii                Use ASCII instead of Unicode symbols");
+    println!("  -h, --help                 Show this help");
+    println!();
+    println!("Components:");
+    for comp in &app.components {
+        println!("  {:<22} {}", comp.short_name(), comp.description);
+    }
+}
+
+/// Prints the detected style of every component, one per line.
+fn print_detected(app: &App) {
+    let width = app
+        .components
+        .iter()
+        .map(|comp| comp.name.len())
+        .max()
+        .unwrap_or(0);
+    for comp in &app.components {
+        match &comp.current_style {
+            Some(style) => println!(
+                "{:<width$}  {}  ({})",
+                comp.name,
+                style.value,
+                style.source,
+                width = width
+            ),
+            None => println!("{:<width$}  -", comp.name, width = width),
+        }
+    }
+}
+
+fn run_headless_restore(app: &App, theme_dir: &Path, cli: &CliArgs) -> Result<()> {
+    let plan = plan_restore(app, theme_dir)?;
+    if plan.is_empty() {
+        anyhow::bail!("Nothing to restore in {}", theme_dir.display());
+    }
+
+    if !cli.yes {
+        println!(
+            "The following destinations will be overwritten (current versions are kept in {}):",
+            backups_dir().display()
+        );
+        for action in &plan {
+            println!("  {}: {}", action.component, action.destination.display());
+        }
+        print!("Restore theme? [y/N] ");
+        io::Write::flush(&mut io::stdout())?;
+
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        if !matches!(answer.trim(), "y" | "Y" | "yes") {
+            anyhow::bail!("Aborted");
+        }
+    }
+
+    restore_theme(&plan, &mut StdoutReporter { quiet: cli.quiet })
+}
+
+/// Creates a theme straight from command-line arguments without touching the
+/// terminal's raw mode.
+fn run_headless(app: &mut App, cli: &CliArgs) -> Result<()> {
+    for (name, path) in &cli.add_components {
+        app.components.push(ThemeComponent::new(
+            name,
+            vec![path.as_str()],
+            "Custom component",
+        ));
+    }
+
+    app.create_archive = cli.archive;
+    app.link_sources = cli.link;
+    app.verify = cli.verify;
+    app.dedupe = cli.dedupe;
+    if let Some(out) = &cli.out {
+        app.theme_directory = expand_tilde(out).to_string_lossy().to_string();
+    }
+
+    if cli.components.is_empty() {
+        return Err(exit_error(
+            EXIT_INVALID_ARGUMENTS,
+            "--components is required in headless mode",
+        ));
+    }
+    for skip in &cli.skip_paths {
+        let skipped = expand_tilde(skip);
+        let mut found = false;
+        for comp in &mut app.components {
+            if let Some(path) = comp
+                .source_paths
+                .iter()
+                .find(|path| expand_path(path) == skipped)
+            {
+                comp.excluded_sources.push(path.clone());
+                found = true;
+            }
+        }
+        if !found {
+            return Err(exit_error(
+                EXIT_INVALID_ARGUMENTS,
+                format!("--skip-path {} is not a source path of any component", skip),
+            ));
+        }
+    }
+    let mut order = Vec::new();
+    for short_name in &cli.components {
+        if short_name == "all" {
+            app.components.iter_mut().for_each(|c| c.checked = true);
+        } else {
+            let index = app
+                .find_component(short_name)
+                .map_err(|e| exit_error(EXIT_INVALID_ARGUMENTS, format!("{:#}", e)))?;
+            app.components[index].checked = true;
+            if !order.contains(&index) {
+                order.push(index);
+            }
+        }
+    }
+    // Export in the order the components were listed
+    if !cli.components.iter().any(|name| name == "all") {
+        let mut listed: Vec<Option<ThemeComponent>> = order.iter().map(|_| None).collect();
+        let mut rest = Vec::new();
+        for (i, comp) in std::mem::take(&mut app.components).into_iter().enumerate() {
+            match order.iter().position(|&index| index == i) {
+                Some(pos) => listed[pos] = Some(comp),
+                None => rest.push(comp),
+            }
+        }
+        app.components = listed.into_iter().flatten().chain(rest).collect();
+    }
+
+    if cli.dry_run {
+        print_dry_run(&dry_run(app));
+        return Ok(());
+    }
+    if let Some(previous) = &cli.diff {
+        let mut reporter = StdoutReporter { quiet: true };
+        print_diff(&diff_export(app, &expand_tilde(previous), &mut reporter)?);
+        return Ok(());
+    }
+
+    let name = cli
+        .name
+        .as_deref()
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .ok_or_else(|| {
+            exit_error(
+                EXIT_INVALID_ARGUMENTS,
+                "--name is required in headless mode",
+            )
+        })?;
+    validate_name("--name", name)
+        .map_err(|e| exit_error(EXIT_INVALID_ARGUMENTS, format!("{:#}", e)))?;
+    app.theme_name = name.to_string();
+
+    app.permission_issues = check_permissions(app);
+    if !app.permission_issues.is_empty() {
+        eprintln!("Permission issues found:");
+        for issue in &app.permission_issues {
+            eprintln!(
+                "  {}: {} ({})",
+                issue.component,
+                issue.path,
+                issue.issue_type.description()
+            );
+        }
+        eprintln!();
+        eprintln!("{}", generate_chmod_commands(&app.permission_issues));
+        return Err(exit_error(
+            EXIT_PERMISSIONS,
+            format!("{} permission issue(s) found", app.permission_issues.len()),
+        ));
+    }
+
+    if !cli.yes {
+        println!("Theme: {}", app.theme_name);
+        println!(
+            "Reading from: {} ({})",
+            source_user(),
+            get_user_home_dir().display()
+        );
+        println!("Directory: {}", app.theme_directory);
+        if app.link_sources {
+            println!("Home files will be moved into the theme and linked back.");
+        }
+        if app.verify {
+            println!("Copied files will be checked against their source.");
+        }
+        if app.dedupe {
+            println!("Identical files within a component will be hardlinked.");
+        }
+        if app.git_add {
+            println!("The export will be staged if it is inside a git repository.");
+        }
+        println!("Components:");
+        for comp in app.checked_components() {
+            println!("  - {}", comp.name);
+        }
+        let size = estimate_size(app);
+        println!("Estimated size: {}", format_size(size));
+        if let Some(note) = flatpak_summary_note(app) {
+            println!("Note: {}", note);
+        }
+        if size > LARGE_EXPORT_BYTES {
+            println!("Warning: this is a large export, check the selected components.");
+        }
+        print!("Create theme? [y/N] ");
+        io::Write::flush(&mut io::stdout())?;
+
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        if !matches!(answer.trim(), "y" | "Y" | "yes") {
+            anyhow::bail!("Aborted");
+        }
+    }
+
+    check_theme_location(app)
+        .map_err(|e| exit_error(EXIT_INVALID_ARGUMENTS, format!("{:#}", e)))?;
+    let result = create_theme(app, &mut StdoutReporter { quiet: cli.quiet })?;
+    if let Err(e) = Session::clear() {
+        log::warn!("{:#}", e);
+    }
+    if result.copied.is_empty() {
+        return Err(exit_error(EXIT_NOTHING_COPIED, "No files were copied"));
+    }
+    if cli.quiet {
+        let theme_dir = Path::new(&app.theme_directory).join(&app.theme_name);
+        println!("{}", std::path::absolute(&theme_dir)?.display());
+    }
+    Ok(())
+}
+
+fn draw_ui(f: &mut Frame, app: &App) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(4),
+            Constraint::Min(0),
+            Constraint::Length(3),
+        ])
+        .split(f.area());
+
+    draw_header(f, chunks[0]);
+
+    // Main content
+    match app.mode {
+        Mode::Selecting => draw_selection(f, app, chunks[1]),
+        Mode::SourcePaths => draw_source_paths(f, app, chunks[1]),
+        Mode::Naming => draw_naming(f, app, chunks[1]),
+        Mode::DirectorySelection => draw_directory_selection(f, app, chunks[1]),
+        Mode::NewDirectory => draw_new_directory(f, app, chunks[1]),
+        Mode::EditPath => draw_edit_path(f, app, chunks[1]),
+        Mode::AddComponentName | Mode::AddComponentPath => draw_add_component(f, app, chunks[1]),
+        Mode::Summary => draw_summary(f, app, chunks[1]),
+        Mode::DryRun => draw_dry_run(f, app, chunks[1]),
+        Mode::Diff => draw_diff(f, app, chunks[1]),
+        Mode::PermissionCheck => draw_permission_check(f, app, chunks[1]),
+        Mode::Restore => draw_restore(f, app, chunks[1]),
+        Mode::RestoreConfirm => draw_restore_confirm(f, app, chunks[1]),
+        Mode::Profiles | Mode::ProfileName => draw_profiles(f, app, chunks[1]),
+        Mode::Results => draw_results(f, app, chunks[1]),
+        Mode::ResumeSession => draw_resume_session(f, app, chunks[1]),
+    }
+
+    // Status
+    let status_text = match app.mode {
+        Mode::Selecting if app.filtering => {
+            format!("Filter: {}_ | Enter: apply, Esc: clear", app.filter)
+        }
+        Mode::Selecting => app.message.clone(),
+        Mode::SourcePaths => "Space: include/exclude the path, Esc/Enter: back".to_string(),
+        Mode::Naming => format!("Name: {}_", app.theme_name),
+        Mode::DirectorySelection => format!(
+            "Path: {} | Enter: open, s: use this directory, e: edit path, Tab: create new, Esc: cancel",
+            app.theme_directory
+        ),
+        Mode::NewDirectory => format!("New directory: {}_", app.new_directory_name),
+        Mode::AddComponentName => "Enter: next, Esc: cancel".to_string(),
+        Mode::AddComponentPath => "Enter: add component, Esc: back".to_string(),
+        Mode::EditPath => format!("Path: {}_ | Enter: go, Esc: cancel", app.path_input),
+        Mode::Summary => {
+            "Enter to create, d: dry run, c: compare with existing, a: toggle .tar.gz archive, l: toggle move and link, v: toggle verify, h: toggle hardlinks, Esc to cancel"
+                .to_string()
+        }
+        Mode::DryRun => "Esc/Enter: back to summary".to_string(),
+        Mode::Diff => format!("{}: Scroll | Esc/Enter: back to summary", glyphs().up_down),
+        Mode::PermissionCheck => {
+            "1: Re-run with sudo, 2: Copy chmod commands, 3: Sudo only for unreadable paths, Esc: Cancel".to_string()
+        }
+        Mode::Restore => app.message.clone(),
+        Mode::RestoreConfirm if !app.message.is_empty() => app.message.clone(),
+        Mode::RestoreConfirm => {
+            "Space: include/exclude the component, y: Restore and overwrite, Esc: Cancel"
+                .to_string()
+        }
+        Mode::ResumeSession => "y: Restore the selection, n/Esc: Start fresh".to_string(),
+        Mode::Profiles => {
+            "Enter: apply, s: save the current selection, d: delete, Esc: back".to_string()
+        }
+        Mode::ProfileName => format!(
+            "Profile name: {}_ | Enter: save, Esc: cancel",
+            app.profile_name
+        ),
+        Mode::Results => {
+            let keys = format!(
+                "{}: Scroll, c: Copy the skipped list, Enter/q: Quit",
+                glyphs().up_down
+            );
+            if app.message.is_empty() {
+                keys
+            } else {
+                format!("{} | {}", app.message, keys)
+            }
+        }
+    };
+
+    let status = Paragraph::new(status_text)
+        .style(Style::default().add_modifier(Modifier::REVERSED))
+        .block(bordered_block());
+    f.render_widget(status, chunks[2]);
+
+    if app.show_help {
+        draw_help(f, app);
+    }
+}
+
+/// The title bar, with the Plasma version and session the themes were
+/// detected in and, below them, whose files are read and with which uid, so a
+/// sudo run reading the wrong home shows at once.
+fn draw_header(f: &mut Frame, area: Rect) {
+    let mut title = vec![Span::styled("Theme Creator", Style::default().bold())];
+    let environment: Vec<String> = plasma_version()
+        .map(|version| format!("Plasma {}", version))
+        .into_iter()
+        .chain(
+            session_type()
+                .label()
+                .map(|session| format!("{} session", session)),
+        )
+        .collect();
+    if !environment.is_empty() {
+        title.push(Span::styled(
+            format!("  {}", environment.join(", ")),
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+
+    let mut identity = vec![
+        Span::styled("Reading from: ", Style::default().bold()),
+        Span::styled(
+            get_user_home_dir().display().to_string(),
+            Style::default().fg(Color::Cyan),
+        ),
+        Span::styled(
+            format!(" ({})", home_origin()),
+            Style::default().fg(Color::DarkGray),
+        ),
+    ];
+    let sudo = if env::var_os("SUDO_USER").is_some() {
+        " via sudo"
+    } else {
+        ""
+    };
+    match effective_uid() {
+        Some(0) => identity.push(Span::styled(
+            format!("  running as root{}", sudo),
+            Style::default().fg(Color::Yellow),
+        )),
+        Some(uid) => identity.push(Span::styled(
+            format!("  running as uid {}{}", uid, sudo),
+            Style::default().fg(Color::DarkGray),
+        )),
+        None => {}
+    }
+
+    let title =
+        Paragraph::new(vec![Line::from(title), Line::from(identity)]).block(bordered_block());
+    f.render_widget(title, area);
+}
+
+/// Splits `text` into spans with the first case-insensitive match of
+/// `pattern` highlighted.
+fn highlight_match<'a>(text: &'a str, pattern: &str, style: Style) -> Vec<Span<'a>> {
+    match find_ignore_case(text, pattern) {
+        Some(start) if !pattern.is_empty() => {
+            let end = start + pattern.len();
+            vec![
+                Span::styled(&text[..start], style),
+                Span::styled(&text[start..end], style.fg(Color::Yellow).bold()),
+                Span::styled(&text[end..], style),
+            ]
+        }
+        _ => vec![Span::styled(text, style)],
+    }
+}
+
+/// Keys available in the current mode, as (key, action) pairs.
+fn help_keys(app: &App) -> Vec<(&'static str, &'static str)> {
+    let mut keys = match app.mode {
+        Mode::Selecting if app.filtering => vec![
+            ("type", "Filter components"),
+            (glyphs().up_down, "Move"),
+            ("Backspace", "Delete a character"),
+            ("Enter", "Apply the filter"),
+            ("Esc", "Clear the filter"),
+        ],
+        Mode::Selecting => vec![
+            (glyphs().up_down, "Move"),
+            (glyphs().shift_up_down, "Reorder (export order)"),
+            ("Space", "Toggle the component"),
+            (
+                glyphs().arrow,
+                "Pick the component's source paths (or move down)",
+            ),
+            ("a / n", "Select all / none"),
+            ("g", "Select or unselect the component's whole group"),
+            ("u", "Undo the last selection change"),
+            ("letter", "Jump to the next component (Shift+letter always)"),
+            ("/", "Filter components"),
+            ("+", "Add a custom component"),
+            ("r", "Restore an exported theme"),
+            ("p", "Apply or save a profile (named selection)"),
+            ("Enter", "Continue"),
+            ("q / Esc", "Quit (Esc clears the filter first)"),
+        ],
+        Mode::SourcePaths => vec![
+            (glyphs().up_down, "Move"),
+            ("Space", "Include or exclude the path"),
+            ("Enter / Esc", "Back to components"),
+        ],
+        Mode::Naming => vec![
+            ("type", "Theme name"),
+            ("Enter", "Continue"),
+            ("Esc", "Back to components"),
+        ],
+        Mode::DirectorySelection => vec![
+            (glyphs().up_down, "Move"),
+            ("Enter", "Open the directory"),
+            ("s", "Save in the current directory"),
+            ("e", "Type a path"),
+            ("Tab", "Create a new directory"),
+            ("Esc", "Back to naming"),
+        ],
+        Mode::NewDirectory => vec![
+            ("type", "Directory name"),
+            ("Enter", "Create it"),
+            ("Esc", "Cancel"),
+        ],
+        Mode::AddComponentName | Mode::AddComponentPath => vec![
+            ("type", "Component name, then its path"),
+            ("Enter", "Next / add the component"),
+            ("Esc", "Back / cancel"),
+        ],
+        Mode::EditPath => vec![
+            ("type", "Path, ~ is your home"),
+            ("Enter", "Go there (twice to create it)"),
+            ("Esc", "Cancel"),
+        ],
+        Mode::Summary => vec![
+            ("Enter", "Create the theme"),
+            ("d", "Dry run"),
+            ("c", "Compare with the existing export of this name"),
+            ("a", "Toggle .tar.gz archive"),
+            ("l", "Toggle moving home files in and linking them back"),
+            ("v", "Toggle checking the copies against their source"),
+            ("h", "Toggle hardlinking identical files"),
+            ("Esc", "Back to components"),
+        ],
+        Mode::DryRun => vec![("Enter / Esc", "Back to the summary")],
+        Mode::Diff => vec![
+            (glyphs().up_down, "Scroll"),
+            ("Enter / Esc", "Back to the summary"),
+        ],
+        Mode::PermissionCheck => vec![
+            ("1", "Re-run with sudo"),
+            ("2", "Copy chmod commands"),
+            ("3", "Export, copying only the unreadable paths with sudo"),
+            ("Esc", "Back to the summary"),
+        ],
+        Mode::Restore => vec![
+            (glyphs().up_down, "Move"),
+            ("Enter", "Restore the theme"),
+            ("Esc", "Back to components"),
+        ],
+        Mode::RestoreConfirm => vec![
+            (glyphs().up_down, "Move"),
+            ("Space", "Include or exclude the component"),
+            ("y", "Restore the included components and overwrite"),
+            ("Esc", "Cancel"),
+        ],
+        Mode::ResumeSession => vec![
+            ("y / Enter", "Restore the last selection"),
+            ("n / Esc", "Start with a fresh selection"),
+        ],
+        Mode::Profiles => vec![
+            (glyphs().up_down, "Move"),
+            ("Enter", "Select the profile's components"),
+            ("s", "Save the current selection as a profile"),
+            ("d", "Delete the profile"),
+            ("Esc", "Back to components"),
+        ],
+        Mode::ProfileName => vec![
+            ("type", "Profile name, an existing one is replaced"),
+            ("Enter", "Save"),
+            ("Esc", "Cancel"),
+        ],
+        Mode::Results => vec![
+            (glyphs().up_down, "Scroll"),
+            ("PgUp / PgDn", "Scroll a page"),
+            ("c", "Copy the skipped files and reasons to the clipboard"),
+            ("Enter / q / Esc", "Quit"),
+        ],
+    };
+    keys.push((if app.is_typing() { "F1" } else { "? / F1" }, "This help"));
+    keys
+}
+
+fn draw_help(f: &mut Frame, app: &App) {
+    let keys = help_keys(app);
+    let key_width = keys
+        .iter()
+        .map(|(key, _)| key.chars().count())
+        .max()
+        .unwrap_or(0);
+    let mut lines: Vec<Line> = keys
+        .iter()
+        .map(|(key, action)| {
+            Line::from(vec![
+                Span::styled(
+                    format!(" {:<width$}  ", key, width = key_width),
+                    Style::default().fg(Color::Yellow),
+                ),
+                Span::raw(*action),
+            ])
+        })
+        .collect();
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled(
+        " Press any key to close",
+        Style::default().fg(Color::DarkGray),
+    )));
+
+    let width = lines.iter().map(Line::width).max().unwrap_or(0) as u16 + 4;
+    let height = lines.len() as u16 + 2;
+    let area = f.area();
+    let popup = Rect {
+        x: area.x + area.width.saturating_sub(width) / 2,
+        y: area.y + area.height.saturating_sub(height) / 2,
+        width: width.min(area.width),
+        height: height.min(area.height),
+    };
+
+    f.render_widget(Clear, popup);
+    let help = Paragraph::new(lines).block(bordered_block().title("Keys"));
+    f.render_widget(help, popup);
+}
+
+fn draw_selection(f: &mut Frame, app: &App, area: Rect) {
+    let visible = app.visible_components();
+    let inner = bordered_block().inner(area);
+    // Room for the detection after its indent and arrow, minus the scrollbar
+    let indent = format!("     {} ", glyphs().arrow);
+    let detection_width = (inner.width as usize)
+        .saturating_sub(indent.chars().count() + 1)
+        .max(1);
+    // A header goes above the first component of each group
+    let mut selected_row = None;
+    let mut items: Vec<ListItem> = Vec::new();
+    for (pos, &i) in visible.iter().enumerate() {
+        let comp = &app.components[i];
+        if pos == 0 || app.components[visible[pos - 1]].category != comp.category {
+            let group = visible
+                .iter()
+                .skip(pos)
+                .take_while(|&&j| app.components[j].category == comp.category);
+            let checked = group
+                .clone()
+                .filter(|&&j| app.components[j].checked)
+                .count();
+            items.push(ListItem::new(Line::from(Span::styled(
+                format!(" {} ({} of {})", comp.category, checked, group.count()),
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            ))));
+        }
+        if i == app.selected {
+            selected_row = Some(items.len());
+        }
+        let checkbox = if comp.checked { "[x]" } else { "[ ]" };
+        let style = if i == app.selected {
+            Style::default().add_modifier(Modifier::REVERSED | Modifier::BOLD)
+        } else {
+            Style::default()
+        };
+
+        let mut name_line = vec![Span::styled(format!(" {} ", checkbox), Style::default())];
+        name_line.extend(highlight_match(&comp.name, &app.filter, style));
+        let mut description_line = vec![Span::styled("     ", Style::default())];
+        description_line.extend(highlight_match(
+            &comp.description,
+            &app.filter,
+            Style::default().fg(Color::DarkGray),
+        ));
+        if !comp.excluded_sources.is_empty() {
+            description_line.push(Span::styled(
+                format!(
+                    " ({} of {} paths)",
+                    comp.active_sources().count(),
+                    comp.source_paths.len()
+                ),
+                Style::default().fg(Color::Yellow),
+            ));
+        }
+
+        let mut content = vec![Line::from(name_line), Line::from(description_line)];
+
+        // Add current style info if available, in full only when selected
+        if let Some(ref current_style) = comp.current_style {
+            let value_style = Style::default().fg(Color::Cyan);
+            let source_style = Style::default().fg(Color::DarkGray);
+            let source = format!("  {}", current_style.source);
+            let rows = if i == app.selected {
+                wrap_detection(&current_style.value, &source, detection_width)
+            } else {
+                let value = truncate_to_width(&current_style.value, detection_width);
+                let room = detection_width - value.chars().count();
+                // The source goes first when space runs out
+                let source = if source.chars().count() <= room {
+                    source
+                } else {
+                    String::new()
+                };
+                vec![(value, source)]
+            };
+            for (row, (value, source)) in rows.into_iter().enumerate() {
+                let lead = if row == 0 {
+                    vec![
+                        Span::styled("     ", Style::default()),
+                        Span::styled(
+                            format!("{} ", glyphs().arrow),
+                            Style::default().fg(Color::Green),
+                        ),
+                    ]
+                } else {
+                    vec![Span::raw(" ".repeat(indent.chars().count()))]
+                };
+                let mut line = lead;
+                line.push(Span::styled(value, value_style));
+                line.push(Span::styled(source, source_style));
+                content.push(Line::from(line));
+            }
+        } else {
+            content.push(Line::from(vec![
+                Span::styled("     ", Style::default()),
+                Span::styled(
+                    format!("{} (none detected)", glyphs().arrow),
+                    Style::default().fg(Color::DarkGray),
+                ),
+            ]));
+        }
+
+        items.push(ListItem::new(content));
+    }
+
+    let mut state = ListState::default()
+        .with_offset(app.selection_offset.get())
+        .with_selected(selected_row);
+
+    let title = if app.filter.is_empty() {
+        "Select Components".to_string()
+    } else {
+        format!(
+            "Select Components (filter: {}, {} of {})",
+            app.filter,
+            visible.len(),
+            app.components.len()
+        )
+    };
+
+    let heights: Vec<usize> = items.iter().map(ListItem::height).collect();
+    let list = List::new(items)
+        .block(bordered_block().title(title))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+
+    f.render_stateful_widget(list, area, &mut state);
+    app.selection_offset.set(state.offset());
+
+    // Components take three lines, more when a long detection is expanded,
+    // and group headers one
+    draw_scrollbar(
+        f,
+        inner,
+        heights.iter().sum(),
+        heights.iter().take(state.offset()).sum(),
+    );
+}
+
+/// `text` cut to `width` characters, ending in an ellipsis when shortened.
+fn truncate_to_width(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    let ellipsis = glyphs().ellipsis;
+    let keep = width.saturating_sub(ellipsis.chars().count());
+    text.chars()
+        .take(keep)
+        .chain(ellipsis.chars())
+        .take(width)
+        .collect()
+}
+
+/// Splits a detection into rows of `width` characters, as (value, source)
+/// pairs so each part keeps its own style.
+fn wrap_detection(value: &str, source: &str, width: usize) -> Vec<(String, String)> {
+    let chars: Vec<(char, bool)> = value
+        .chars()
+        .map(|c| (c, false))
+        .chain(source.chars().map(|c| (c, true)))
+        .collect();
+    chars
+        .chunks(width)
+        .map(|row| {
+            let part = |is_source: bool| {
+                row.iter()
+                    .filter(|(_, source)| *source == is_source)
+                    .map(|(c, _)| c)
+                    .collect()
+            };
+            (part(false), part(true))
+        })
+        .collect()
+}
+
+fn draw_naming(f: &mut Frame, app: &App, area: Rect) {
+    let text = vec![
+        Line::from("Enter theme name:"),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("> ", Style::default().fg(Color::Green)),
+            Span::styled(&app.theme_name, Style::default()),
+            Span::styled("_", Style::default().fg(Color::Green)),
+        ]),
+        Line::from(""),
+        Line::from(Span::styled(&app.message, Style::default().fg(Color::Red))),
+    ];
+
+    let paragraph = Paragraph::new(text).block(bordered_block().title("Name Theme"));
+    f.render_widget(paragraph, area);
+}
+
+fn draw_directory_selection(f: &mut Frame, app: &App, area: Rect) {
+    let block = bordered_block().title("Select Directory");
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(5),
+            Constraint::Min(1),
+            Constraint::Length(2),
+        ])
+        .split(inner);
+
+    let header = vec![
+        Line::from("Choose where to save your theme:"),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("Current: ", Style::default().fg(Color::Yellow)),
+            Span::styled(&app.theme_directory, Style::default().fg(Color::Cyan)),
+        ]),
+        Line::from(""),
+        match &app.directory_error {
+            Some(error) => Line::from(Span::styled(error, Style::default().fg(Color::Red))),
+            None if !Path::new(&app.theme_directory).exists() => {
+                Line::from("Doesn't exist yet, it will be created.")
+            }
+            None if app.directory_entries.iter().all(|entry| entry == "../") => {
+                Line::from("No subdirectories.")
+            }
+            None => Line::from("Directories:"),
+        },
+    ];
+    f.render_widget(Paragraph::new(header), chunks[0]);
+
+    let items: Vec<ListItem> = app
+        .directory_entries
+        .iter()
+        .map(|entry| {
+            let prefix = if entry.ends_with('/') {
+                glyphs().folder
+            } else {
+                glyphs().file
+            };
+            ListItem::new(Line::from(vec![
+                Span::styled("  ", Style::default()),
+                Span::styled(format!("{} ", prefix), Style::default()),
+                Span::styled(entry, Style::default()),
+            ]))
+        })
+        .collect();
+
+    let mut state = ListState::default()
+        .with_offset(app.directory_offset.get())
+        .with_selected((!app.directory_entries.is_empty()).then_some(app.directory_selected));
+    let list = List::new(items)
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED | Modifier::BOLD));
+    f.render_stateful_widget(list, chunks[1], &mut state);
+    app.directory_offset.set(state.offset());
+    draw_scrollbar(f, chunks[1], app.directory_entries.len(), state.offset());
+
+    let hint = Paragraph::new(vec![
+        Line::from(""),
+        Line::from(format!(
+            "{}: Navigate | Enter: Open | s: Use this directory | e: Type a path | Tab: Create new directory",
+            glyphs().up_down
+        )),
+    ]);
+    f.render_widget(hint, chunks[2]);
+}
+
+/// Draws a vertical scrollbar along the right edge of `area` when a list of
+/// `len` items starting at `offset` doesn't fit.
+fn draw_scrollbar(f: &mut Frame, area: Rect, len: usize, offset: usize) {
+    if len <= area.height as usize {
+        return;
+    }
+    let mut state = ScrollbarState::new(len).position(offset);
+    f.render_stateful_widget(
+        Scrollbar::new(ScrollbarOrientation::VerticalRight).symbols(glyphs().scrollbar.clone()),
+        area,
+        &mut state,
+    );
+}
+
+fn draw_new_directory(f: &mut Frame, app: &App, area: Rect) {
+    let text = vec![
+        Line::from(vec![
+            Span::styled("Create a new directory in: ", Style::default()),
+            Span::styled(&app.theme_directory, Style::default().fg(Color::Cyan)),
+        ]),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("> ", Style::default().fg(Color::Green)),
+            Span::styled(&app.new_directory_name, Style::default()),
+            Span::styled("_", Style::default().fg(Color::Green)),
+        ]),
+        Line::from(""),
+        Line::from(Span::styled(
+            &app.message,
+            Style::default().fg(Color::Yellow),
+        )),
+    ];
+
+    let paragraph = Paragraph::new(text).block(bordered_block().title("New Directory"));
+    f.render_widget(paragraph, area);
+}
+
+fn draw_add_component(f: &mut Frame, app: &App, area: Rect) {
+    let cursor = |active: bool| if active { "_" } else { "" };
+    let naming = app.mode == Mode::AddComponentName;
+
+    let text = vec![
+        Line::from("Component name:"),
+        Line::from(vec![
+            Span::styled("> ", Style::default().fg(Color::Green)),
+            Span::styled(&app.new_component_name, Style::default()),
+            Span::styled(cursor(naming), Style::default().fg(Color::Green)),
+        ]),
+        Line::from(""),
+        Line::from("Source path (~ is expanded):"),
+        Line::from(vec![
+            Span::styled("> ", Style::default().fg(Color::Green)),
+            Span::styled(&app.new_component_path, Style::default()),
+            Span::styled(cursor(!naming), Style::default().fg(Color::Green)),
+        ]),
+        Line::from(""),
+        Line::from(Span::styled(
+            &app.message,
+            Style::default().fg(Color::Yellow),
+        )),
+    ];
+
+    let paragraph = Paragraph::new(text).block(bordered_block().title("Add Component"));
+    f.render_widget(paragraph, area);
+}
+
+fn draw_edit_path(f: &mut Frame, app: &App, area: Rect) {
+    let text = vec![
+        Line::from("Type a destination directory (~ is expanded):"),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("> ", Style::default().fg(Color::Green)),
+            Span::styled(&app.path_input, Style::default()),
+            Span::styled("_", Style::default().fg(Color::Green)),
+        ]),
+        Line::from(""),
+        Line::from(Span::styled(
+            &app.message,
+            Style::default().fg(Color::Yellow),
+        )),
+    ];
+
+    let paragraph = Paragraph::new(text).block(bordered_block().title("Edit Path"));
+    f.render_widget(paragraph, area);
+}
+
+fn draw_summary(f: &mut Frame, app: &App, area: Rect) {
+    let checked = app.checked_components();
+
+    let mut lines = vec![
+        Line::from(vec![
+            Span::styled("Theme: ", Style::default().bold()),
+            Span::styled(&app.theme_name, Style::default().fg(Color::Cyan)),
+        ]),
+        Line::from(vec![
+            Span::styled("Reading from: ", Style::default().bold()),
+            Span::styled(
+                format!("{} ({})", source_user(), get_user_home_dir().display()),
+                Style::default().fg(Color::Cyan),
+            ),
+        ]),
+        Line::from(vec![
+            Span::styled("Archive: ", Style::default().bold()),
+            if app.create_archive {
+                Span::styled(
+                    format!("{}.tar.gz", app.theme_name),
+                    Style::default().fg(Color::Cyan),
+                )
+            } else {
+                Span::styled("no", Style::default().fg(Color::DarkGray))
+            },
+        ]),
+        Line::from(vec![
+            Span::styled("Home files: ", Style::default().bold()),
+            if app.link_sources {
+                Span::styled(
+                    "moved into the theme and linked back",
+                    Style::default().fg(Color::Yellow),
+                )
+            } else {
+                Span::styled("copied", Style::default().fg(Color::DarkGray))
+            },
+        ]),
+        Line::from(vec![
+            Span::styled("Verify copies: ", Style::default().bold()),
+            if app.verify {
+                Span::styled("yes", Style::default().fg(Color::Cyan))
+            } else {
+                Span::styled("no", Style::default().fg(Color::DarkGray))
+            },
+        ]),
+        Line::from(vec![
+            Span::styled("Identical files: ", Style::default().bold()),
+            if app.dedupe {
+                Span::styled("hardlinked", Style::default().fg(Color::Cyan))
+            } else {
+                Span::styled("copied", Style::default().fg(Color::DarkGray))
+            },
+        ]),
+        Line::from(vec![
+            Span::styled("Estimated size: ", Style::default().bold()),
+            if app.estimated_size > LARGE_EXPORT_BYTES {
+                Span::styled(
+                    format!(
+                        "{} (large export, check the selected paths)",
+                        format_size(app.estimated_size)
+                    ),
+                    Style::default().fg(Color::Red).bold(),
+                )
+            } else {
+                Span::styled(
+                    format_size(app.estimated_size),
+                    Style::default().fg(Color::Cyan),
+                )
+            },
+        ]),
+        Line::from(""),
+    ];
+    if app.git_add {
+        lines.insert(
+            lines.len() - 1,
+            Line::from(vec![
+                Span::styled("Git: ", Style::default().bold()),
+                Span::styled(
+                    "stage the export if it is in a repository (--git-add)",
+                    Style::default().fg(Color::Cyan),
+                ),
+            ]),
+        );
+    }
+
+    if let Err(e) = check_theme_location(app) {
+        lines.push(Line::from(Span::styled(
+            format!("{} {}", glyphs().warning, e),
+            Style::default().fg(Color::Red).bold(),
+        )));
+        lines.push(Line::from(""));
+    }
+
+    if let Some(note) = flatpak_summary_note(app) {
+        lines.push(Line::from(Span::styled(
+            format!("{} {}", glyphs().warning, note),
+            Style::default().fg(Color::Yellow),
+        )));
+        lines.push(Line::from(""));
+    }
+
+    if checked.is_empty() {
+        lines.push(Line::from("No components selected!"));
+    } else {
+        lines.push(Line::from("Components to include:"));
+        for comp in checked {
+            lines.push(Line::from(vec![
+                Span::styled(
+                    format!("{} ", glyphs().check),
+                    Style::default().fg(Color::Green),
+                ),
+                Span::styled(&comp.name, Style::default().bold()),
+            ]));
+            lines.push(Line::from(vec![
+                Span::styled("  ", Style::default()),
+                Span::styled(&comp.description, Style::default().fg(Color::DarkGray)),
+            ]));
+        }
+    }
+
+    let paragraph = Paragraph::new(lines)
+        .block(bordered_block().title("Summary"))
+        .wrap(Wrap { trim: true });
+    f.render_widget(paragraph, area);
+}
+
+fn draw_source_paths(f: &mut Frame, app: &App, area: Rect) {
+    let comp = &app.components[app.selected];
+    let mut lines = vec![
+        Line::from(Span::styled(
+            "Unticked paths are left out of the export.",
+            Style::default().fg(Color::Yellow),
+        )),
+        Line::from(""),
+    ];
+
+    for (i, path_str) in comp.source_paths.iter().enumerate() {
+        let checkbox = if comp.excluded_sources.contains(path_str) {
+            "[ ]"
+        } else {
+            "[x]"
+        };
+        let style = if i == app.source_selected {
+            Style::default().add_modifier(Modifier::REVERSED | Modifier::BOLD)
+        } else {
+            Style::default()
+        };
+        let path = expand_path(path_str);
+        let detail = if path.exists() {
+            Span::styled(
+                format!("  {}", path.display()),
+                Style::default().fg(Color::DarkGray),
+            )
+        } else {
+            Span::styled("  (not found)", Style::default().fg(Color::DarkGray))
+        };
+        lines.push(Line::from(vec![
+            Span::styled(format!(" {} ", checkbox), Style::default()),
+            Span::styled(path_str.as_str(), style),
+            detail,
+        ]));
+    }
+
+    let paragraph = Paragraph::new(lines)
+        .block(bordered_block().title(format!("Source Paths: {}", comp.name)))
+        .wrap(Wrap { trim: true });
+    f.render_widget(paragraph, area);
+}
+
+fn draw_dry_run(f: &mut Frame, app: &App, area: Rect) {
+    let mut lines = Vec::new();
+    let mut current_component = "";
+    let (mut total_files, mut total_bytes) = (0, 0);
+
+    for entry in &app.dry_run_report {
+        if entry.component != current_component {
+            if !current_component.is_empty() {
+                lines.push(Line::from(""));
+            }
+            current_component = &entry.component;
+            lines.push(Line::from(Span::styled(
+                current_component,
+                Style::default().bold(),
+            )));
+        }
+
+        let detail = match entry.size {
+            Some((files, bytes)) => {
+                total_files += files;
+                total_bytes += bytes;
+                Span::styled(
+                    format!(" ({} files, {})", files, format_size(bytes)),
+                    Style::default().fg(Color::Green),
+                )
+            }
+            None => Span::styled(" (not found)", Style::default().fg(Color::DarkGray)),
+        };
+        lines.push(Line::from(vec![
+            Span::styled("  ", Style::default()),
+            Span::styled(
+                entry.path.display().to_string(),
+                Style::default().fg(Color::Cyan),
+            ),
+            detail,
+        ]));
+    }
+
+    lines.push(Line::from(""));
+    lines.push(Line::from(vec![
+        Span::styled("Total: ", Style::default().bold()),
+        Span::styled(
+            format!("{} files, {}", total_files, format_size(total_bytes)),
+            Style::default().fg(Color::Yellow),
+        ),
+    ]));
+
+    let paragraph = Paragraph::new(lines)
+        .block(bordered_block().title("Dry Run (nothing is written)"))
+        .wrap(Wrap { trim: true });
+    f.render_widget(paragraph, area);
+}
+
+fn draw_results(f: &mut Frame, app: &App, area: Rect) {
+    let Some(result) = &app.export_result else {
+        return;
+    };
+    let mut lines = vec![
+        Line::from(vec![
+            Span::styled("Saved at: ", Style::default().bold()),
+            Span::styled(
+                result.theme_dir.display().to_string(),
+                Style::default().fg(Color::Cyan),
+            ),
+        ]),
+        Line::from(""),
+        Line::from(Span::styled(
+            format!("Copied ({})", result.copied.len()),
+            Style::default().fg(Color::Green).bold(),
+        )),
+    ];
+    if result.copied.is_empty() {
+        lines.push(Line::from(Span::styled(
+            format!(
+                "  {} No files were copied. Check the paths and permissions.",
+                glyphs().warning
+            ),
+            Style::default().fg(Color::Red),
+        )));
+    }
+    for entry in &result.copied {
+        lines.push(Line::from(format!("  {} {}", glyphs().check, entry)));
+    }
+
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled(
+        format!("Skipped ({})", result.skipped.len()),
+        Style::default().fg(Color::Yellow).bold(),
+    )));
+    for entry in &result.skipped {
+        lines.push(Line::from(Span::styled(
+            format!("  {} {}", glyphs().warning, entry),
+            Style::default().fg(Color::Yellow),
+        )));
+    }
+
+    if !result.verify_failures.is_empty() {
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled(
+            format!("Failed verification ({})", result.verify_failures.len()),
+            Style::default().fg(Color::Red).bold(),
+        )));
+        for failure in &result.verify_failures {
+            lines.push(Line::from(Span::styled(
+                format!("  {} {}", glyphs().cross, failure),
+                Style::default().fg(Color::Red),
+            )));
+        }
+    }
+
+    let paragraph = Paragraph::new(lines)
+        .block(bordered_block().title(format!("Export Results: {}", app.theme_name)))
+        .scroll((app.results_scroll, 0));
+    f.render_widget(paragraph, area);
+}
+
+fn draw_diff(f: &mut Frame, app: &App, area: Rect) {
+    let count = |change| {
+        app.diff_report
+            .iter()
+            .filter(|entry| entry.change == change)
+            .count()
+    };
+    let mut lines = vec![
+        Line::from(vec![
+            Span::styled(
+                format!("{} added", count(Change::Added)),
+                Style::default().fg(Color::Green),
+            ),
+            Span::raw(", "),
+            Span::styled(
+                format!("{} removed", count(Change::Removed)),
+                Style::default().fg(Color::Red),
+            ),
+            Span::raw(", "),
+            Span::styled(
+                format!("{} modified", count(Change::Modified)),
+                Style::default().fg(Color::Yellow),
+            ),
+        ]),
+        Line::from(""),
+    ];
+
+    if app.diff_report.is_empty() {
+        lines.push(Line::from("No changes since the last export."));
+    }
+    for entry in &app.diff_report {
+        let (marker, color) = match entry.change {
+            Change::Added => ("+", Color::Green),
+            Change::Removed => ("-", Color::Red),
+            Change::Modified => ("~", Color::Yellow),
+        };
+        lines.push(Line::from(Span::styled(
+            format!("{} {}", marker, entry.path.display()),
+            Style::default().fg(color),
+        )));
+    }
+
+    let title = format!("Changes since {}", app.theme_name);
+    let paragraph = Paragraph::new(lines)
+        .block(bordered_block().title(title))
+        .scroll((app.diff_scroll, 0));
+    f.render_widget(paragraph, area);
+}
+
+fn draw_permission_check(f: &mut Frame, app: &App, area: Rect) {
+    let mut lines = vec![
+        Line::from(vec![Span::styled(
+            "Permission Issues Found",
+            Style::default().fg(Color::Red).bold(),
+        )]),
+        Line::from(""),
+    ];
+
+    if app.permission_issues.is_empty() {
+        lines.push(Line::from("No permission issues detected!"));
+    } else {
+        lines.push(Line::from(
+            "The following components have permission issues:",
+        ));
+        lines.push(Line::from(""));
+
+        for (i, issue) in app.permission_issues.iter().enumerate() {
+            let issue_text = issue.issue_type.description();
+
+            lines.push(Line::from(vec![
+                Span::styled(format!("{}.", i + 1), Style::default().fg(Color::Yellow)),
+                Span::styled(" ", Style::default()),
+                Span::styled(&issue.component, Style::default().bold()),
+                Span::styled(
+                    format!(" ({})", issue_text),
+                    Style::default().fg(Color::Red),
+                ),
+            ]));
+            lines.push(Line::from(vec![
+                Span::styled("   Path: ", Style::default()),
+                Span::styled(&issue.path, Style::default().fg(Color::Blue)),
+            ]));
+            lines.push(Line::from(""));
+        }
+
+        lines.push(Line::from(vec![Span::styled(
+            "Options:",
+            Style::default().bold(),
+        )]));
+        lines.push(Line::from("1. Re-run with sudo privileges"));
+        lines.push(Line::from("2. Copy chmod commands to clipboard"));
+        lines.push(Line::from(
+            "3. Export as yourself and copy only the unreadable paths with sudo",
+        ));
+        lines.push(Line::from("Esc. Cancel and go back"));
+    }
+
+    let paragraph = Paragraph::new(lines)
+        .block(bordered_block().title("Permission Check"))
+        .wrap(Wrap { trim: true });
+    f.render_widget(paragraph, area);
+}
+
+fn draw_profiles(f: &mut Frame, app: &App, area: Rect) {
+    let mut lines = vec![Line::from(vec![
+        Span::styled("Profiles in: ", Style::default().fg(Color::Yellow)),
+        Span::styled(
+            Profiles::path().display().to_string(),
+            Style::default().fg(Color::Cyan),
+        ),
+    ])];
+    if !app.message.is_empty() {
+        lines.push(Line::from(Span::styled(
+            app.message.as_str(),
+            Style::default().fg(Color::Yellow),
+        )));
+    }
+    lines.push(Line::from(""));
+
+    if app.profiles.is_empty() {
+        lines.push(Line::from(
+            "No profiles yet. Press s to save the current selection.",
+        ));
+    } else {
+        for (i, profile) in app.profiles.iter().enumerate() {
+            let style = if i == app.profile_selected {
+                Style::default().add_modifier(Modifier::REVERSED | Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+            lines.push(Line::from(vec![
+                Span::styled("  ", Style::default()),
+                Span::styled(profile.name.as_str(), style),
+                Span::styled(
+                    format!("  {}", profile.components.join(", ")),
+                    Style::default().fg(Color::DarkGray),
+                ),
+            ]));
+        }
+    }
+
+    let paragraph = Paragraph::new(lines)
+        .block(bordered_block().title("Profiles"))
+        .wrap(Wrap { trim: true });
+    f.render_widget(paragraph, area);
+}
+
+fn draw_restore(f: &mut Frame, app: &App, area: Rect) {
+    let mut lines = vec![
+        Line::from(vec![
+            Span::styled("Themes in: ", Style::default().fg(Color::Yellow)),
+            Span::styled(&app.theme_directory, Style::default().fg(Color::Cyan)),
+        ]),
+        Line::from(""),
+    ];
+
+    if app.restore_entries.is_empty() {
+        lines.push(Line::from("No exported themes found."));
+    } else {
+        for (i, entry) in app.restore_entries.iter().enumerate() {
+            let style = if i == app.restore_selected {
+                Style::default().add_modifier(Modifier::REVERSED | Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+            lines.push(Line::from(vec![
+                Span::styled("  ", Style::default()),
+                Span::styled(entry, style),
+            ]));
+        }
+    }
+
+    let paragraph = Paragraph::new(lines)
+        .block(bordered_block().title("Restore Theme"))
+        .wrap(Wrap { trim: true });
+    f.render_widget(paragraph, area);
+}
+
+/// Every component of the theme with the paths it would overwrite, each
+/// ticked or unticked, so system paths can be reviewed before restoring.
+fn draw_restore_confirm(f: &mut Frame, app: &App, area: Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(0)])
+        .split(area);
+
+    let warning = Paragraph::new(Line::from(Span::styled(
+        format!(
+            "Restoring overwrites existing files (the current ones are kept in {})",
+            backups_dir().display()
+        ),
+        Style::default().fg(Color::Red).bold(),
+    )))
+    .block(bordered_block())
+    .wrap(Wrap { trim: true });
+    f.render_widget(warning, chunks[0]);
+
+    let home = get_user_home_dir();
+    let components = app.restore_components();
+    let items: Vec<ListItem> = components
+        .iter()
+        .enumerate()
+        .map(|(i, component)| {
+            let included = !app.restore_skipped.iter().any(|c| c == component);
+            let style = if i == app.restore_component {
+                Style::default().add_modifier(Modifier::REVERSED | Modifier::BOLD)
+            } else {
+                Style::default().bold()
+            };
+            let mut lines = vec![Line::from(vec![
+                Span::raw(if included { " [x] " } else { " [ ] " }),
+                Span::styled(component.to_string(), style),
+            ])];
+            for action in app
+                .restore_plan
+                .iter()
+                .filter(|action| action.component == *component)
+            {
+                let destination = action.destination.display().to_string();
+                let mut line = vec![
+                    Span::raw("     "),
+                    Span::styled(
+                        format!("{} ", glyphs().arrow),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                ];
+                if action.destination.starts_with(&home) {
+                    line.push(Span::styled(destination, Style::default().fg(Color::Cyan)));
+                } else {
+                    line.push(Span::styled(
+                        destination,
+                        Style::default().fg(Color::Yellow),
+                    ));
+                    line.push(Span::styled(
+                        " (system path)",
+                        Style::default().fg(Color::Yellow).bold(),
+                    ));
+                }
+                lines.push(Line::from(line));
+            }
+            if !included {
+                lines = lines
+                    .into_iter()
+                    .map(|line| line.style(Style::default().add_modifier(Modifier::DIM)))
+                    .collect();
+            }
+            ListItem::new(lines)
+        })
+        .collect();
+
+    let included = components
+        .iter()
+        .filter(|c| !app.restore_skipped.iter().any(|skipped| skipped == *c))
+        .count();
+    let mut state = ListState::default()
+        .with_offset(app.restore_offset.get())
+        .with_selected(Some(app.restore_component));
+    let list = List::new(items).block(bordered_block().title(format!(
+        "Confirm Restore ({} of {} components)",
+        included,
+        components.len()
+    )));
+    f.render_stateful_widget(list, chunks[1], &mut state);
+    app.restore_offset.set(state.offset());
+}
+
+fn draw_resume_session(f: &mut Frame, app: &App, area: Rect) {
+    let Some(session) = &app.saved_session else {
+        return;
+    };
+    let mut lines = vec![
+        Line::from(Span::styled(
+            "The last session ended before a theme was created",
+            Style::default().fg(Color::Yellow).bold(),
+        )),
+        Line::from(""),
+    ];
+    if !session.theme_name.is_empty() {
+        lines.push(Line::from(vec![
+            Span::styled("Theme name: ", Style::default().bold()),
+            Span::styled(&session.theme_name, Style::default().fg(Color::Cyan)),
+        ]));
+    }
+    lines.push(Line::from(format!(
+        "Selected components ({}):",
+        session.checked.len()
+    )));
+    for name in &session.checked {
+        lines.push(Line::from(vec![
+            Span::styled(
+                format!("{} ", glyphs().check),
+                Style::default().fg(Color::Green),
+            ),
+            Span::styled(name, Style::default().bold()),
+        ]));
+    }
+
+    let paragraph = Paragraph::new(lines)
+        .block(bordered_block().title("Restore Last Session"))
+        .wrap(Wrap { trim: true });
+    f.render_widget(paragraph, area);
+}
+
+/// Asks what to do about an entry that failed to copy, over the progress.
+fn draw_copy_failure(f: &mut Frame, path: &str, error: &str) {
+    let lines = vec![
+        Line::from(Span::styled(path, Style::default().fg(Color::Cyan))),
+        Line::from(Span::styled(error, Style::default().fg(Color::Red))),
+        Line::from(""),
+        Line::from("r: Retry, s: Skip, a: Skip all failures, Esc: Abort the export"),
+    ];
+
+    let area = f.area();
+    let width = (lines.iter().map(Line::width).max().unwrap_or(0) as u16 + 4).min(area.width);
+    let inner_width = width.saturating_sub(2).max(1) as usize;
+    let rows: usize = lines
+        .iter()
+        .map(|line| line.width().max(1).div_ceil(inner_width))
+        .sum();
+    let height = (rows as u16 + 2).min(area.height);
+    let popup = Rect {
+        x: area.x + area.width.saturating_sub(width) / 2,
+        y: area.y + area.height.saturating_sub(height) / 2,
+        width,
+        height,
+    };
+
+    f.render_widget(Clear, popup);
+    let paragraph = Paragraph::new(lines)
+        .block(bordered_block().title("Copy failed"))
+        .wrap(Wrap { trim: false });
+    f.render_widget(paragraph, popup);
+}
+
+fn draw_progress(f: &mut Frame, progress: &CopyProgress, lines: &[String], cancellable: bool) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(4),
+            Constraint::Length(3),
+            Constraint::Length(3),
+            Constraint::Min(0),
+        ])
+        .split(f.area());
+
+    draw_header(f, chunks[0]);
+
+    let component = Gauge::default()
+        .block(bordered_block().title(progress.component.as_str()))
+        .gauge_style(Style::default().fg(Color::Cyan))
+        .ratio(progress.component_ratio())
+        .label(format!(
+            "{}/{} files",
+            progress.component_done, progress.component_total
+        ));
+    f.render_widget(component, chunks[1]);
+
+    let overall = Gauge::default()
+        .block(bordered_block().title(if cancellable {
+            "Overall (Esc to cancel)"
+        } else {
+            "Overall"
+        }))
+        .gauge_style(Style::default().fg(Color::Green))
+        .ratio(progress.overall_ratio())
+        .label(format!(
+            "{}/{} files",
+            progress.overall_done, progress.overall_total
+        ));
+    f.render_widget(overall, chunks[2]);
+
+    let visible = chunks[3].height.saturating_sub(2) as usize;
+    let log: Vec<Line> = lines
+        .iter()
+        .skip(lines.len().saturating_sub(visible))
+        .map(|line| Line::from(line.as_str()))
+        .collect();
+    let paragraph = Paragraph::new(log).block(bordered_block());
+    f.render_widget(paragraph, chunks[3]);
+}
+
+/// Exit codes for scripts using the headless mode; other failures exit
+/// with 1.
+const EXIT_PERMISSIONS: u8 = 2;
+const EXIT_NOTHING_COPIED: u8 = 3;
+const EXIT_INVALID_ARGUMENTS: u8 = 4;
+
+/// An error that ends the program with a specific exit code.
+#[derive(Debug)]
+struct ExitError {
+    code: u8,
+    message: String,
+}
+
+impl std::fmt::Display for ExitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ExitError {}
+
+fn exit_error(code: u8, message: impl Into<String>) -> anyhow::Error {
+    ExitError {
+        code,
+        message: message.into(),
+    }
+    .into()
+}
+
+fn main() -> std::process::ExitCode {
+    match run() {
+        Ok(()) => std::process::ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
+            let code = e.downcast_ref::<ExitError>().map_or(1, |e| e.code);
+            std::process::ExitCode::from(code)
+        }
+    }
+}
+
+fn run() -> Result<()> {
+    let mut cli = CliArgs::parse(env::args().skip(1))
+        .map_err(|e| exit_error(EXIT_INVALID_ARGUMENTS, format!("{:#}", e)))?;
+    let _ = ASCII_OUTPUT.set(cli.ascii || !unicode_locale());
+    if cli.home.is_some() && cli.user.is_some() {
+        return Err(exit_error(
+            EXIT_INVALID_ARGUMENTS,
+            "--home and --user can't be combined",
+        ));
+    }
+    if let Some(home) = &cli.home {
+        let _ = HOME_OVERRIDE.set(std::path::PathBuf::from(home));
+    }
+    if let Some(user) = &cli.user {
+        let home = lookup_passwd_home(user)
+            .ok_or_else(|| exit_error(EXIT_INVALID_ARGUMENTS, format!("Unknown user {}", user)))?;
+        let _ = HOME_OVERRIDE.set(home);
+        let _ = USER_OVERRIDE.set(user.clone());
+    }
+    init_logging(cli.verbose);
+    log::info!(
+        "kde-copycat {} started, home {}",
+        env!("CARGO_PKG_VERSION"),
+        get_user_home_dir().display()
+    );
+    let mut app = App::new();
+    app.manifest_format = cli.output_format;
+    app.git_add = cli.git_add;
+    if home_dir().is_none() {
+        log::error!("No home directory found (HOME, SUDO_USER, USER and /home)");
+        app.message = "No home directory found, so nothing was detected (try --home)".to_string();
+    }
+
+    if cli.help {
+        print_usage(&app);
+        return Ok(());
+    }
+
+    if cli.list_detected {
+        print_detected(&app);
+        return Ok(());
+    }
+
+    if let Some(theme_dir) = &cli.restore {
+        return run_headless_restore(&app, &expand_tilde(theme_dir), &cli);
+    }
+
+    if let Some(from) = cli.from.clone() {
+        let path = expand_tilde(&from);
+        Recipe::load(&path)
+            .and_then(|recipe| recipe.apply(&path, &app, &mut cli))
+            .map_err(|e| exit_error(EXIT_INVALID_ARGUMENTS, format!("{:#}", e)))?;
+    }
+
+    if cli.is_headless() {
+        return run_headless(&mut app, &cli);
+    }
+
+    // Log lines would garble the TUI, they still go to the log file
+    set_console_logging(false);
+
+    // Pipes, CI logs and dumb terminals can't show the TUI
+    let dumb = env::var("TERM").is_ok_and(|term| term == "dumb");
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() || dumb {
+        set_console_logging(true);
+        anyhow::bail!(
+            "The interactive TUI needs a terminal. Use --list-detected to see what was \
+             detected, or export without the TUI with --name and --components (see --help)."
+        );
+    }
+
+    // Initialize terminal with error handling
+    let result = (|| -> Result<()> {
+        enable_raw_mode().context("Failed to enable raw mode")?;
+        let mut stdout = io::stdout();
+        execute!(stdout, EnterAlternateScreen).context("Failed to enter the alternate screen")?;
+        // Pasted text arrives as one event instead of keys that could press Enter
+        let _ = execute!(stdout, EnableBracketedPaste);
+        let backend = CrosstermBackend::new(stdout);
+        let mut terminal = Terminal::new(backend)?;
+
+        if let Some(session) = Session::load() {
+            app.saved_session = Some(session);
+            app.mode = Mode::ResumeSession;
+        }
+
+        // Run the main app loop
+        run_app_loop(&mut terminal, &mut app)?;
+
+        terminal.show_cursor()?;
+        Ok(())
+    })();
+
+    // Leave the terminal usable whether or not the TUI failed
+    let _ = disable_raw_mode();
+    let _ = execute!(
+        io::stdout(),
+        DisableBracketedPaste,
+        LeaveAlternateScreen,
+        crossterm::cursor::Show
+    );
+    set_console_logging(true);
+
+    if let Err(e) = result {
+        // `main` prints it, with the cause after the hint
+        return Err(e.context(
+            "Terminal error. Make sure you're running this in a proper terminal, \
+             or use --list-detected or --name and --components to run without the TUI",
+        ));
+    }
+
+    for line in &app.exit_report {
+        println!("{}", line);
+    }
+
+    Ok(())
+}
+
+fn run_app_loop(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    app: &mut App,
+) -> Result<()> {
+    // Nothing on screen changes by itself, so only input needs a redraw
+    let mut dirty = true;
+    let mut session = Session::of(app);
+    loop {
+        // Saved on every change, except over the one still being offered
+        if dirty && app.mode != Mode::ResumeSession && app.mode != Mode::Results {
+            let current = Session::of(app);
+            if current != session {
+                if let Err(e) = current.save() {
+                    log::warn!("{:#}", e);
+                }
+                session = current;
+            }
+        }
+
+        if dirty {
+            terminal.draw(|f| draw_ui(f, app))?;
+            dirty = false;
+        }
+
+        if event::poll(std::time::Duration::from_millis(100))
+            .context("Failed to poll for events")?
+        {
+            let event = event::read()?;
+            dirty = matches!(event, Event::Key(_) | Event::Resize(..) | Event::Paste(_));
+            if let Event::Resize(width, height) = event {
+                resize_terminal(terminal, width, height)?;
+            }
+            if let Event::Paste(text) = &event {
+                app.paste(text);
+            }
+            if let Event::Key(key) = event {
+                if key.kind == KeyEventKind::Press {
+                    if app.show_help {
+                        app.show_help = false;
+                        continue;
+                    }
+                    if key.code == KeyCode::F(1)
+                        || (key.code == KeyCode::Char('?') && !app.is_typing())
+                    {
+                        app.show_help = true;
+                        continue;
+                    }
+                    match app.mode {
+                        Mode::Selecting if app.filtering => match key.code {
+                            KeyCode::Esc => {
+                                app.filter.clear();
+                                app.filtering = false;
+                            }
+                            KeyCode::Enter => app.filtering = false,
+                            KeyCode::Up => app.prev(),
+                            KeyCode::Down => app.next(),
+                            KeyCode::Backspace => {
+                                app.filter.pop();
+                                app.update_filter();
+                            }
+                            KeyCode::Char(c) => {
+                                app.filter.push(c);
+                                app.update_filter();
+                            }
+                            _ => {}
+                        },
+                        Mode::Selecting => match key.code {
+                            KeyCode::Esc if !app.filter.is_empty() => {
+                                app.filter.clear();
+                                app.update_filter();
+                            }
+                            KeyCode::Char('q') | KeyCode::Esc => break,
+                            KeyCode::Char('/') => app.filtering = true,
+                            KeyCode::Char('+') => {
+                                app.new_component_name.clear();
+                                app.new_component_path.clear();
+                                app.message = String::new();
+                                app.mode = Mode::AddComponentName;
+                            }
+                            KeyCode::Char('a') => app.set_all_checked(true),
+                            KeyCode::Char('n') => app.set_all_checked(false),
+                            KeyCode::Char('u') => app.undo_selection(),
+                            KeyCode::Char('g') => app.toggle_category(),
+                            KeyCode::Up if key.modifiers.contains(KeyModifiers::SHIFT) => {
+                                app.move_selected(false)
+                            }
+                            KeyCode::Down if key.modifiers.contains(KeyModifiers::SHIFT) => {
+                                app.move_selected(true)
+                            }
+                            KeyCode::Up | KeyCode::Left => app.prev(),
+                            // Enter continues to naming, so the picker is on
+                            // Right, for components with more than one path
+                            KeyCode::Right
+                                if app
+                                    .components
+                                    .get(app.selected)
+                                    .is_some_and(|comp| comp.source_paths.len() > 1) =>
+                            {
+                                app.source_selected = 0;
+                                app.mode = Mode::SourcePaths;
+                            }
                             KeyCode::Down | KeyCode::Right => app.next(),
                             KeyCode::Char(' ') => app.toggle(),
+                            KeyCode::Char('r') => {
+                                update_restore_entries(app);
+                                app.message = "Enter: choose theme, Esc: back".to_string();
+                                app.mode = Mode::Restore;
+                            }
+                            KeyCode::Char('p') => match Profiles::load() {
+                                Ok(profiles) => {
+                                    app.profiles = profiles.profiles;
+                                    app.profile_selected = 0;
+                                    app.message.clear();
+                                    app.mode = Mode::Profiles;
+                                }
+                                Err(e) => app.message = format!("{:#}", e),
+                            },
+                            // Letters without a command jump; Shift+letter always
+                            // does, so components starting with a, g, n, p, q, r
+                            // or u can be reached too
+                            KeyCode::Char(c) if c.is_alphanumeric() => app.jump_to_letter(c),
+                            KeyCode::Enter => {
+                                if app.checked_components().is_empty() {
+                                    app.message = "Select at least one component".to_string();
+                                } else {
+                                    app.message.clear();
+                                    app.mode = Mode::Naming;
+                                }
+                            }
+                            _ => {}
+                        },
+                        Mode::SourcePaths => {
+                            let count = app.components[app.selected].source_paths.len();
+                            match key.code {
+                                KeyCode::Esc | KeyCode::Enter | KeyCode::Left => {
+                                    app.mode = Mode::Selecting;
+                                }
+                                KeyCode::Up if count > 0 => {
+                                    app.source_selected = (app.source_selected + count - 1) % count;
+                                }
+                                KeyCode::Down if count > 0 => {
+                                    app.source_selected = (app.source_selected + 1) % count;
+                                }
+                                KeyCode::Char(' ') => {
+                                    let selected = app.source_selected;
+                                    app.components[app.selected].toggle_source(selected);
+                                }
+                                _ => {}
+                            }
+                        }
+                        Mode::Naming => {
+                            match key.code {
+                                KeyCode::Esc => {
+                                    app.message = SELECTING_HINT.to_string();
+                                    app.mode = Mode::Selecting;
+                                }
+                                KeyCode::Enter => {
+                                    // The name becomes a folder under the target directory
+                                    match validate_name("Theme name", &app.theme_name) {
+                                        Ok(()) => {
+                                            app.theme_name = app.theme_name.trim().to_string();
+                                            app.message.clear();
+                                            update_directory_entries(app);
+                                            app.mode = Mode::DirectorySelection;
+                                        }
+                                        Err(e) => app.message = e.to_string(),
+                                    }
+                                }
+                                KeyCode::Backspace => {
+                                    app.theme_name.pop();
+                                }
+                                KeyCode::Char(c) => app.theme_name.push(c),
+                                _ => {}
+                            }
+                        }
+                        Mode::DirectorySelection => {
+                            match key.code {
+                                KeyCode::Esc => {
+                                    app.message.clear();
+                                    app.mode = Mode::Naming;
+                                }
+                                KeyCode::Enter => {
+                                    let selected_entry = if !app.directory_entries.is_empty()
+                                        && app.directory_selected < app.directory_entries.len()
+                                    {
+                                        app.directory_entries.get(app.directory_selected).cloned()
+                                    } else {
+                                        None
+                                    };
+
+                                    if let Some(entry) = selected_entry {
+                                        if entry == "../" {
+                                            if let Some(parent) = parent_directory(
+                                                std::path::Path::new(&app.theme_directory),
+                                            ) {
+                                                app.theme_directory =
+                                                    parent.to_string_lossy().to_string();
+                                            }
+                                            update_directory_entries(app);
+                                        } else if entry.ends_with('/') {
+                                            // Navigate into subdirectory
+                                            let new_path =
+                                                std::path::Path::new(&app.theme_directory)
+                                                    .join(entry.trim_end_matches('/'));
+                                            app.theme_directory =
+                                                new_path.to_string_lossy().to_string();
+                                            app.directory_selected = 0;
+                                            update_directory_entries(app);
+                                        }
+                                    } else {
+                                        accept_directory(app);
+                                    }
+                                }
+                                KeyCode::Up if !app.directory_entries.is_empty() => {
+                                    app.directory_selected = if app.directory_selected == 0 {
+                                        app.directory_entries.len() - 1
+                                    } else {
+                                        app.directory_selected - 1
+                                    };
+                                }
+                                KeyCode::Down if !app.directory_entries.is_empty() => {
+                                    app.directory_selected =
+                                        (app.directory_selected + 1) % app.directory_entries.len();
+                                }
+                                KeyCode::Char('s') => accept_directory(app),
+                                KeyCode::Char('e') => {
+                                    app.path_input = app.theme_directory.clone();
+                                    app.pending_create_path = None;
+                                    app.message = String::new();
+                                    app.mode = Mode::EditPath;
+                                }
+                                KeyCode::Tab => {
+                                    app.new_directory_name.clear();
+                                    app.message = "Enter to create, Esc to cancel".to_string();
+                                    app.mode = Mode::NewDirectory;
+                                }
+                                _ => {}
+                            }
+                        }
+                        Mode::NewDirectory => match key.code {
+                            KeyCode::Esc => app.mode = Mode::DirectorySelection,
+                            KeyCode::Enter => match create_new_directory(app) {
+                                Ok(()) => {
+                                    update_directory_entries(app);
+                                    app.mode = Mode::DirectorySelection;
+                                }
+                                Err(e) => app.message = format!("{:#}", e),
+                            },
+                            KeyCode::Backspace => {
+                                app.new_directory_name.pop();
+                            }
+                            KeyCode::Char(c) => app.new_directory_name.push(c),
+                            _ => {}
+                        },
+                        Mode::AddComponentName => match key.code {
+                            KeyCode::Esc => {
+                                app.message = SELECTING_HINT.to_string();
+                                app.mode = Mode::Selecting;
+                            }
+                            KeyCode::Enter => {
+                                let name = app.new_component_name.trim();
+                                if name.is_empty() {
+                                    app.message = "Name cannot be empty".to_string();
+                                } else if app
+                                    .components
+                                    .iter()
+                                    .any(|c| c.name.eq_ignore_ascii_case(name))
+                                {
+                                    app.message = format!("A component named {} exists", name);
+                                } else {
+                                    app.message = String::new();
+                                    app.mode = Mode::AddComponentPath;
+                                }
+                            }
+                            KeyCode::Backspace => {
+                                app.new_component_name.pop();
+                            }
+                            KeyCode::Char(c) => app.new_component_name.push(c),
+                            _ => {}
+                        },
+                        Mode::AddComponentPath => match key.code {
+                            KeyCode::Esc => app.mode = Mode::AddComponentName,
+                            KeyCode::Enter => match app.add_custom_component() {
+                                Ok(()) => app.mode = Mode::Selecting,
+                                Err(e) => app.message = format!("{:#}", e),
+                            },
+                            KeyCode::Backspace => {
+                                app.new_component_path.pop();
+                            }
+                            KeyCode::Char(c) => app.new_component_path.push(c),
+                            _ => {}
+                        },
+                        Mode::EditPath => match key.code {
+                            KeyCode::Esc => app.mode = Mode::DirectorySelection,
+                            KeyCode::Enter => match apply_path_input(app) {
+                                Ok(true) => {
+                                    update_directory_entries(app);
+                                    app.mode = Mode::DirectorySelection;
+                                }
+                                Ok(false) => {}
+                                Err(e) => app.message = format!("{:#}", e),
+                            },
+                            KeyCode::Backspace => {
+                                app.path_input.pop();
+                                app.pending_create_path = None;
+                            }
+                            KeyCode::Char(c) => {
+                                app.path_input.push(c);
+                                app.pending_create_path = None;
+                            }
+                            _ => {}
+                        },
+                        Mode::DryRun => match key.code {
+                            KeyCode::Esc | KeyCode::Enter => app.mode = Mode::Summary,
+                            _ => {}
+                        },
+                        Mode::Results => match key.code {
+                            KeyCode::Enter | KeyCode::Esc | KeyCode::Char('q') => break,
+                            KeyCode::Up => {
+                                app.results_scroll = app.results_scroll.saturating_sub(1)
+                            }
+                            KeyCode::Down => {
+                                app.results_scroll = app.results_scroll.saturating_add(1)
+                            }
+                            KeyCode::PageUp => {
+                                app.results_scroll = app.results_scroll.saturating_sub(10)
+                            }
+                            KeyCode::PageDown => {
+                                app.results_scroll = app.results_scroll.saturating_add(10)
+                            }
+                            KeyCode::Char('c') => {
+                                let skipped = app
+                                    .export_result
+                                    .as_ref()
+                                    .map(|result| result.skipped.join("\n"))
+                                    .unwrap_or_default();
+                                app.message = if skipped.is_empty() {
+                                    "Nothing was skipped".to_string()
+                                } else if copy_to_clipboard(&skipped).is_ok() {
+                                    "Skipped list copied to clipboard".to_string()
+                                } else {
+                                    "No clipboard tool found, the list is also in theme_info.txt"
+                                        .to_string()
+                                };
+                            }
+                            _ => {}
+                        },
+                        Mode::Diff => match key.code {
+                            KeyCode::Esc | KeyCode::Enter => app.mode = Mode::Summary,
+                            KeyCode::Up => app.diff_scroll = app.diff_scroll.saturating_sub(1),
+                            KeyCode::Down => app.diff_scroll = app.diff_scroll.saturating_add(1),
+                            KeyCode::PageUp => app.diff_scroll = app.diff_scroll.saturating_sub(10),
+                            KeyCode::PageDown => {
+                                app.diff_scroll = app.diff_scroll.saturating_add(10)
+                            }
+                            _ => {}
+                        },
+                        Mode::Summary => match key.code {
+                            KeyCode::Esc => app.mode = Mode::Selecting,
+                            KeyCode::Char('a') => app.create_archive = !app.create_archive,
+                            KeyCode::Char('l') => app.link_sources = !app.link_sources,
+                            KeyCode::Char('v') => app.verify = !app.verify,
+                            KeyCode::Char('h') => app.dedupe = !app.dedupe,
+                            KeyCode::Char('d') => {
+                                app.dry_run_report = dry_run(app);
+                                app.mode = Mode::DryRun;
+                            }
+                            KeyCode::Char('c') => {
+                                let previous =
+                                    Path::new(&app.theme_directory).join(&app.theme_name);
+                                let mut reporter = TuiReporter::new(terminal).cancellable();
+                                reporter.log(&format!(
+                                    "{} Comparing with {}...",
+                                    glyphs().busy,
+                                    previous.display()
+                                ));
+                                match diff_export(app, &previous, &mut reporter) {
+                                    Ok(report) => {
+                                        app.diff_report = report;
+                                        app.diff_scroll = 0;
+                                        app.mode = Mode::Diff;
+                                    }
+                                    Err(e) => app.message = format!("{:#}", e),
+                                }
+                            }
+                            // The summary says why the folder can't be used
+                            KeyCode::Enter if check_theme_location(app).is_err() => {}
+                            KeyCode::Enter => {
+                                app.permission_issues = check_permissions(app);
+                                if app.permission_issues.is_empty() {
+                                    let mut reporter = TuiReporter::new(terminal).cancellable();
+                                    match create_theme(app, &mut reporter) {
+                                        Err(e) if e.is::<Cancelled>() => {
+                                            app.message = e.to_string();
+                                        }
+                                        result => {
+                                            let result = result?;
+                                            app.exit_report = reporter.into_lines();
+                                            show_results(app, result);
+                                        }
+                                    }
+                                } else {
+                                    app.mode = Mode::PermissionCheck;
+                                }
+                            }
+                            _ => {}
+                        },
+                        Mode::PermissionCheck => {
+                            match key.code {
+                                KeyCode::Esc => app.mode = Mode::Summary,
+                                KeyCode::Char('1') => {
+                                    // Re-run headless with sudo; hand over everything the
+                                    // child would otherwise re-detect as root
+                                    let current_exe =
+                                        env::current_exe().context("Failed to get current exe")?;
+                                    let args = sudo_args(app);
+
+                                    // sudo needs a cooked terminal for its password prompt
+                                    suspend_tui(terminal)?;
+                                    let status =
+                                        Command::new("sudo").arg(current_exe).args(&args).status();
+                                    let succeeded = matches!(&status, Ok(s) if s.success());
+                                    if succeeded {
+                                        break;
+                                    }
+                                    resume_tui(terminal)?;
+
+                                    app.message = match status {
+                                        Ok(_) => "Sudo execution failed".to_string(),
+                                        Err(e) => format!("Failed to run sudo: {}", e),
+                                    };
+                                    app.mode = Mode::Selecting;
+                                }
+                                KeyCode::Char('2') => {
+                                    // Generate chmod commands
+                                    let chmod_commands =
+                                        generate_chmod_commands(&app.permission_issues);
+
+                                    // Try to copy to clipboard
+                                    if let Ok(()) = copy_to_clipboard(&chmod_commands) {
+                                        app.message =
+                                            "Chmod commands copied to clipboard!".to_string();
+                                    } else {
+                                        println!(
+                                            "\n=== Chmod Commands ===\n{}\n=== End Commands ===\n",
+                                            chmod_commands
+                                        );
+                                        app.message =
+                                            "Chmod commands printed to terminal".to_string();
+                                    }
+                                    app.mode = Mode::Selecting;
+                                }
+                                KeyCode::Char('3') => {
+                                    app.sudo_unreadable = true;
+                                    let mut reporter = TuiReporter::new(terminal).cancellable();
+                                    match create_theme(app, &mut reporter) {
+                                        Err(e) if e.is::<Cancelled>() => {
+                                            app.message = e.to_string();
+                                            app.mode = Mode::Summary;
+                                        }
+                                        result => {
+                                            let result = result?;
+                                            app.exit_report = reporter.into_lines();
+                                            show_results(app, result);
+                                        }
+                                    }
+                                }
+                                _ => {}
+                            }
+                        }
+                        Mode::Restore => match key.code {
+                            KeyCode::Esc => {
+                                app.message = SELECTING_HINT.to_string();
+                                app.mode = Mode::Selecting;
+                            }
+                            KeyCode::Up if !app.restore_entries.is_empty() => {
+                                app.restore_selected = if app.restore_selected == 0 {
+                                    app.restore_entries.len() - 1
+                                } else {
+                                    app.restore_selected - 1
+                                };
+                            }
+                            KeyCode::Down if !app.restore_entries.is_empty() => {
+                                app.restore_selected =
+                                    (app.restore_selected + 1) % app.restore_entries.len();
+                            }
+                            KeyCode::Enter => {
+                                if let Some(entry) = app.restore_entries.get(app.restore_selected) {
+                                    let theme_dir =
+                                        std::path::Path::new(&app.theme_directory).join(entry);
+                                    match plan_restore(app, &theme_dir) {
+                                        Ok(plan) if plan.is_empty() => {
+                                            app.message =
+                                                "Nothing to restore in this theme".to_string();
+                                        }
+                                        Ok(plan) => {
+                                            app.restore_plan = plan;
+                                            app.restore_skipped.clear();
+                                            app.restore_component = 0;
+                                            app.restore_offset.set(0);
+                                            app.message.clear();
+                                            app.mode = Mode::RestoreConfirm;
+                                        }
+                                        Err(e) => app.message = format!("{:#}", e),
+                                    }
+                                }
+                            }
+                            _ => {}
+                        },
+                        Mode::Profiles => match key.code {
+                            KeyCode::Esc => {
+                                app.message = SELECTING_HINT.to_string();
+                                app.mode = Mode::Selecting;
+                            }
+                            KeyCode::Up if !app.profiles.is_empty() => {
+                                app.profile_selected = if app.profile_selected == 0 {
+                                    app.profiles.len() - 1
+                                } else {
+                                    app.profile_selected - 1
+                                };
+                            }
+                            KeyCode::Down if !app.profiles.is_empty() => {
+                                app.profile_selected =
+                                    (app.profile_selected + 1) % app.profiles.len();
+                            }
                             KeyCode::Enter => {
+                                if let Some(profile) = app.profiles.get(app.profile_selected) {
+                                    let profile = profile.clone();
+                                    app.apply_profile(&profile);
+                                    app.mode = Mode::Selecting;
+                                }
+                            }
+                            KeyCode::Char('s') => {
                                 if app.checked_components().is_empty() {
                                     app.message = "Select at least one component".to_string();
                                 } else {
-                                    app.mode = Mode::Naming;
+                                    app.profile_name.clear();
+                                    app.message.clear();
+                                    app.mode = Mode::ProfileName;
+                                }
+                            }
+                            KeyCode::Char('d') if app.profile_selected < app.profiles.len() => {
+                                let removed = app.profiles.remove(app.profile_selected);
+                                app.profile_selected = app
+                                    .profile_selected
+                                    .min(app.profiles.len().saturating_sub(1));
+                                let saved = Profiles {
+                                    profiles: app.profiles.clone(),
                                 }
+                                .save();
+                                app.message = match saved {
+                                    Ok(()) => format!("Deleted profile {}", removed.name),
+                                    Err(e) => format!("{:#}", e),
+                                };
                             }
                             _ => {}
                         },
-                        Mode::Naming => {
+                        Mode::ProfileName => match key.code {
+                            KeyCode::Esc => {
+                                app.message.clear();
+                                app.mode = Mode::Profiles;
+                            }
+                            KeyCode::Enter => {
+                                let name = app.profile_name.trim().to_string();
+                                if name.is_empty() {
+                                    app.message = "Name cannot be empty".to_string();
+                                } else {
+                                    app.message = match app.save_profile(&name) {
+                                        Ok(()) => format!("Saved profile {}", name),
+                                        Err(e) => format!("{:#}", e),
+                                    };
+                                    app.mode = Mode::Profiles;
+                                }
+                            }
+                            KeyCode::Backspace => {
+                                app.profile_name.pop();
+                            }
+                            KeyCode::Char(c) => app.profile_name.push(c),
+                            _ => {}
+                        },
+                        Mode::ResumeSession => match key.code {
+                            KeyCode::Char('y') | KeyCode::Enter => {
+                                if let Some(session) = app.saved_session.take() {
+                                    session.apply(app);
+                                }
+                                app.mode = Mode::Selecting;
+                            }
+                            KeyCode::Char('n') | KeyCode::Esc => {
+                                app.saved_session = None;
+                                if let Err(e) = Session::clear() {
+                                    log::warn!("{:#}", e);
+                                }
+                                app.mode = Mode::Selecting;
+                            }
+                            _ => {}
+                        },
+                        Mode::RestoreConfirm => {
+                            let count = app.restore_components().len();
                             match key.code {
-                                KeyCode::Esc => app.mode = Mode::Selecting,
-                                KeyCode::Enter => {
-                                    if app.theme_name.trim().is_empty() {
-                                        // Stay in naming mode
-                                    } else {
-                                        update_directory_entries(app);
-                                        app.mode = Mode::DirectorySelection;
+                                KeyCode::Esc => {
+                                    app.message = "Enter: choose theme, Esc: back".to_string();
+                                    app.mode = Mode::Restore;
+                                }
+                                KeyCode::Up if count > 0 => {
+                                    app.restore_component =
+                                        (app.restore_component + count - 1) % count;
+                                }
+                                KeyCode::Down if count > 0 => {
+                                    app.restore_component = (app.restore_component + 1) % count;
+                                }
+                                KeyCode::Char(' ') => {
+                                    app.message.clear();
+                                    let Some(component) = app
+                                        .restore_components()
+                                        .get(app.restore_component)
+                                        .map(|c| c.to_string())
+                                    else {
+                                        continue;
+                                    };
+                                    match app.restore_skipped.iter().position(|c| *c == component) {
+                                        Some(index) => {
+                                            app.restore_skipped.remove(index);
+                                        }
+                                        None => app.restore_skipped.push(component),
                                     }
                                 }
-                                KeyCode::Backspace => {
-                                    app.theme_name.pop();
+                                KeyCode::Char('y') => {
+                                    let plan = app.included_restore_plan();
+                                    if plan.is_empty() {
+                                        app.message = "No components selected".to_string();
+                                        continue;
+                                    }
+                                    let mut reporter = TuiReporter::new(terminal);
+                                    restore_theme(&plan, &mut reporter)?;
+                                    app.exit_report = reporter.into_lines();
+                                    break;
                                 }
-                                KeyCode::Char(c) => app.theme_name.push(c),
                                 _ => {}
                             }
                         }
-                        Mode::DirectorySelection => {
-                            match key.code {
-                                KeyCode::Esc => app.mode = Mode::Naming,
-                                KeyCode::Enter => {
-                                    let selected_entry = if !app.directory_entries.is_empty()
-                                        && app.directory_selected < app.directory_entries.len()
-                                    {
-                                        app.directory_entries.get(app.directory_selected).cloned()
-                                    } else {
-                                        None
-                                    };
+                    }
+                }
+            }
+        }
+    }
+
+    // Only a TUI that was interrupted leaves its session to resume
+    Session::clear()
+}
+
+/// Command line for the headless child started by "Re-run with sudo".
+fn sudo_args(app: &App) -> Vec<String> {
+    let mut args = vec![
+        "--name".to_string(),
+        app.theme_name.clone(),
+        "--out".to_string(),
+        app.theme_directory.clone(),
+    ];
+    match USER_OVERRIDE.get() {
+        Some(user) => args.extend(["--user".to_string(), user.clone()]),
+        None => args.extend([
+            "--home".to_string(),
+            get_user_home_dir().to_string_lossy().to_string(),
+        ]),
+    }
+
+    for comp in app.components.iter().filter(|c| c.runtime) {
+        if let Some(path) = comp.source_paths.first() {
+            args.push("--add-component".to_string());
+            args.push(format!("{}={}", comp.name, expand_path(path).display()));
+        }
+    }
+
+    let short_names: Vec<String> = app
+        .checked_components()
+        .iter()
+        .map(|c| c.short_name())
+        .collect();
+    args.push("--components".to_string());
+    args.push(short_names.join(","));
+
+    if app.create_archive {
+        args.push("--archive".to_string());
+    }
+    if app.link_sources {
+        args.push("--link".to_string());
+    }
+    if app.verify {
+        args.push("--verify".to_string());
+    }
+    if app.dedupe {
+        args.push("--dedupe".to_string());
+    }
+    if app.git_add {
+        args.push("--git-add".to_string());
+    }
+    if app.manifest_format != ManifestFormat::Json {
+        args.push("--output-format".to_string());
+        args.push(app.manifest_format.name().to_string());
+    }
+    for comp in app.checked_components() {
+        for path in &comp.excluded_sources {
+            args.push("--skip-path".to_string());
+            args.push(expand_path(path).display().to_string());
+        }
+    }
+    if *ASCII_OUTPUT.get().unwrap_or(&false) {
+        args.push("--ascii".to_string());
+    }
+    args.push("--yes".to_string());
+    args
+}
+
+fn suspend_tui(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>) -> Result<()> {
+    disable_raw_mode()?;
+    execute!(
+        terminal.backend_mut(),
+        DisableBracketedPaste,
+        LeaveAlternateScreen
+    )?;
+    terminal.show_cursor()?;
+    Ok(())
+}
+
+fn resume_tui(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>) -> Result<()> {
+    enable_raw_mode()?;
+    execute!(
+        terminal.backend_mut(),
+        EnterAlternateScreen,
+        EnableBracketedPaste
+    )?;
+    terminal.clear()?;
+    Ok(())
+}
+
+/// Adopts the size from a resize event and clears the screen, so the next
+/// draw lays everything out again and repaints it in full instead of diffing
+/// against what the old size left behind.
+fn resize_terminal(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    width: u16,
+    height: u16,
+) -> Result<()> {
+    terminal.resize(Rect::new(0, 0, width, height))?;
+    Ok(())
+}
+
+/// Shows what a finished export copied and skipped.
+fn show_results(app: &mut App, result: ExportResult) {
+    if let Err(e) = Session::clear() {
+        log::warn!("{:#}", e);
+    }
+    app.export_result = Some(result);
+    app.results_scroll = 0;
+    app.message.clear();
+    app.mode = Mode::Results;
+}
+
+/// Uses the current directory for the export and remembers it for the next
+/// run.
+fn accept_directory(app: &mut App) {
+    let state = State {
+        theme_directory: Some(app.theme_directory.clone()),
+    };
+    if let Err(e) = state.save() {
+        log::warn!("{:#}", e);
+    }
+    app.estimated_size = estimate_size(app);
+    app.mode = Mode::Summary;
+}
+
+fn update_directory_entries(app: &mut App) {
+    app.directory_entries.clear();
+    app.directory_selected = 0;
+    app.directory_offset.set(0);
+
+    let path = std::path::Path::new(&app.theme_directory);
+    // The filesystem root has no parent to go back to
+    if parent_directory(path).is_some() {
+        app.directory_entries.push("../".to_string());
+    }
+
+    app.directory_error = None;
+    match fs::read_dir(path) {
+        Ok(entries) => {
+            let first_child = app.directory_entries.len();
+            for entry in entries.flatten() {
+                if let Ok(file_type) = entry.file_type() {
+                    let name = entry.file_name().to_string_lossy().to_string();
+                    if file_type.is_dir() && !name.starts_with('.') {
+                        app.directory_entries.push(name + "/");
+                    }
+                }
+            }
+            app.directory_entries[first_child..].sort();
+        }
+        // The export creates it
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => {
+            log::warn!("Cannot read {}: {}", path.display(), e);
+            app.directory_error = Some(format!("Cannot read directory: {}", e.kind()));
+        }
+    }
+}
+
+/// Parent of `path`, resolving relative paths against the current directory.
+fn parent_directory(path: &Path) -> Option<std::path::PathBuf> {
+    std::path::absolute(path)
+        .ok()?
+        .parent()
+        .map(Path::to_path_buf)
+}
+
+/// Lists exported themes (folders with a `theme_info.txt`) in the theme directory.
+fn update_restore_entries(app: &mut App) {
+    app.restore_entries.clear();
+    app.restore_selected = 0;
+
+    if let Ok(entries) = fs::read_dir(&app.theme_directory) {
+        for entry in entries.flatten() {
+            if entry.path().join("theme_info.txt").is_file() {
+                app.restore_entries
+                    .push(entry.file_name().to_string_lossy().to_string());
+            }
+        }
+        app.restore_entries.sort();
+    }
+}
+
+/// Maps every entry of every component folder in `theme_dir` back to the
+/// source path it was copied from. Entries are matched by file name, and
+/// user-level paths win over system ones when several sources share a name.
+fn plan_restore(app: &App, theme_dir: &Path) -> Result<Vec<RestoreAction>> {
+    if !theme_dir.join("theme_info.txt").is_file() {
+        anyhow::bail!("{} is not an exported theme", theme_dir.display());
+    }
+
+    let mut plan = Vec::new();
+    for comp in &app.components {
+        let component_dir = theme_dir.join(comp.dir_name());
+        let Ok(entries) = fs::read_dir(&component_dir) else {
+            continue;
+        };
+
+        for entry in entries.flatten() {
+            if let Some(destination) = comp.restore_destination(&entry.path()) {
+                plan.push(RestoreAction {
+                    component: comp.name.clone(),
+                    source: entry.path(),
+                    destination: expand_path(&destination),
+                });
+            }
+        }
+    }
+
+    Ok(plan)
+}
+
+/// Copies every planned entry back to its destination and reports the
+/// restored components.
+fn restore_theme(plan: &[RestoreAction], reporter: &mut dyn Reporter) -> Result<()> {
+    let mut restored: Vec<&str> = Vec::new();
+    let mut failed = Vec::new();
+    let backup_root =
+        backups_dir().join(chrono::Local::now().format("%Y-%m-%d_%H-%M-%S").to_string());
+    let mut backups = 0;
+
+    reporter.log(&format!("{} Restoring theme...", glyphs().busy));
+    reporter.log("");
+
+    let mut progress = CopyProgress {
+        overall_total: plan
+            .iter()
+            .map(|action| count_files(&action.source, &GlobSet::empty()))
+            .sum(),
+        ..Default::default()
+    };
+
+    for action in plan {
+        reporter.log(&format!(
+            "{} {}: {} -> {}",
+            glyphs().folder,
+            action.component,
+            action.source.display(),
+            action.destination.display()
+        ));
+        progress.start_component(
+            &action.component,
+            count_files(&action.source, &GlobSet::empty()),
+        );
+        reporter.progress(&progress);
+
+        let parent = action
+            .destination
+            .parent()
+            .context("Invalid destination path")?;
+        let result = backup_existing(&action.source, &action.destination, &backup_root)
+            .map(|count| {
+                if count > 0 {
+                    reporter.log(&format!("   Backed up {} existing entries", count));
+                    backups += count;
+                }
+            })
+            .and_then(|_| Ok(fs::create_dir_all(parent)?))
+            .and_then(|_| {
+                copy_as(
+                    &action.source,
+                    &action.destination,
+                    &GlobSet::empty(),
+                    &mut |_, _| {
+                        progress.advance();
+                        reporter.progress(&progress);
+                        Ok(())
+                    },
+                )
+            });
+
+        match result {
+            Ok(skipped) => {
+                log::info!(
+                    "{}: restored {}",
+                    action.component,
+                    action.destination.display()
+                );
+                for (path, e) in skipped {
+                    reporter.log(&format!(
+                        "   {} Skipped {}: {}",
+                        glyphs().warning,
+                        path.display(),
+                        e
+                    ));
+                    failed.push(format!("{}: {} ({})", action.component, path.display(), e));
+                }
+                reporter.log(&format!("   {} Restored", glyphs().check));
+                if !restored.contains(&action.component.as_str()) {
+                    restored.push(&action.component);
+                }
+            }
+            Err(e) => {
+                log::warn!(
+                    "{}: failed to restore {}: {:#}",
+                    action.component,
+                    action.destination.display(),
+                    e
+                );
+                reporter.log(&format!("   {} Failed to restore: {}", glyphs().cross, e));
+                failed.push(format!(
+                    "{}: {} ({})",
+                    action.component,
+                    action.destination.display(),
+                    e
+                ));
+            }
+        }
+    }
+
+    reporter.log("");
+    reporter.log(&"=".repeat(60));
+    reporter.log(&format!("Components restored: {}", restored.len()));
+    for component in &restored {
+        reporter.log(&format!("  - {}", component));
+    }
+    if !failed.is_empty() {
+        reporter.log(&format!("Failed to restore: {}", failed.len()));
+        for failure in &failed {
+            reporter.log(&format!("  - {}", failure));
+        }
+    }
+    if backups > 0 {
+        reporter.log(&format!("Previous versions backed up: {}", backups));
+        reporter.log(&format!("  - {}", backup_root.display()));
+    }
+    reporter.log(&"=".repeat(60));
+
+    Ok(())
+}
+
+/// Folder that restores keep the files they overwrite in, one timestamped
+/// folder per restore.
+fn backups_dir() -> std::path::PathBuf {
+    log_path().with_file_name("backups")
+}
+
+/// Copies the entries of `dest` that restoring `source` over it would
+/// overwrite into `backup_root`, under their absolute path, so
+/// `~/.config/kwinrc` is kept as `<backup_root>/home/<user>/.config/kwinrc`.
+/// Folders that are merged into are descended into rather than copied
+/// whole. Returns the number of entries backed up.
+fn backup_existing(source: &Path, dest: &Path, backup_root: &Path) -> Result<usize> {
+    // The source itself is followed, like `copy_as` does
+    let source_is_dir = fs::metadata(source)?.is_dir();
+    backup_entry(source, source_is_dir, dest, backup_root)
+        .with_context(|| format!("Failed to back up {}", dest.display()))
+}
+
+fn backup_entry(
+    source: &Path,
+    source_is_dir: bool,
+    dest: &Path,
+    backup_root: &Path,
+) -> Result<usize> {
+    let Ok(metadata) = fs::symlink_metadata(dest) else {
+        return Ok(0);
+    };
+    if source_is_dir && metadata.is_dir() {
+        let mut count = 0;
+        for entry in fs::read_dir(source)? {
+            let entry = entry?;
+            let is_dir = entry.file_type()?.is_dir();
+            count += backup_entry(
+                &entry.path(),
+                is_dir,
+                &dest.join(entry.file_name()),
+                backup_root,
+            )?;
+        }
+        return Ok(count);
+    }
+
+    let backup = backup_root.join(dest.strip_prefix("/").unwrap_or(dest));
+    if let Some(parent) = backup.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    // A partial backup isn't safe to overwrite the original with
+    let mut skipped = Vec::new();
+    copy_entry(
+        dest,
+        &metadata,
+        &backup,
+        &GlobSet::empty(),
+        &mut |_, _| Ok(()),
+        &mut skipped,
+    )?;
+    if let Some((path, e)) = skipped.pop() {
+        return Err(e.context(format!("Failed to copy {}", path.display())));
+    }
+    log::info!("Backed up {} to {}", dest.display(), backup.display());
+    Ok(1)
+}
+
+/// Checks that `name` is a single, visible folder name, so joining it to a
+/// directory can't escape it. `what` names the field in the error.
+fn validate_name(what: &str, name: &str) -> Result<()> {
+    let name = name.trim();
+    if name.is_empty() {
+        anyhow::bail!("{} cannot be empty", what);
+    }
+    if name.contains(['/', '\\']) {
+        anyhow::bail!("{} cannot contain '/' or '\\'", what);
+    }
+    if name.starts_with('.') {
+        anyhow::bail!("{} cannot start with '.'", what);
+    }
+    if name.chars().any(char::is_control) {
+        anyhow::bail!("{} cannot contain control characters", what);
+    }
+    Ok(())
+}
+
+/// Creates `app.new_directory_name` under the current theme directory and
+/// moves into it.
+fn create_new_directory(app: &mut App) -> Result<()> {
+    let name = app.new_directory_name.trim();
+    validate_name("Directory name", name)?;
+
+    let new_path = std::path::Path::new(&app.theme_directory).join(name);
+    fs::create_dir(&new_path)
+        .with_context(|| format!("Failed to create {}", new_path.display()))?;
+
+    app.theme_directory = new_path.to_string_lossy().to_string();
+    app.new_directory_name.clear();
+    Ok(())
+}
+
+/// Switches the theme directory to the typed path. A missing directory is
+/// only created after Enter is pressed a second time for the same path.
+/// Returns whether the directory changed.
+fn apply_path_input(app: &mut App) -> Result<bool> {
+    let input = app.path_input.trim();
+    if input.is_empty() {
+        anyhow::bail!("Path cannot be empty");
+    }
+
+    let path = expand_tilde(input);
+    if path.exists() && !path.is_dir() {
+        anyhow::bail!("{} is not a directory", path.display());
+    }
+
+    if !path.exists() {
+        if app.pending_create_path.as_ref() != Some(&path) {
+            app.message = format!(
+                "{} does not exist. Press Enter again to create it.",
+                path.display()
+            );
+            app.pending_create_path = Some(path);
+            return Ok(false);
+        }
+        fs::create_dir_all(&path)
+            .with_context(|| format!("Failed to create {}", path.display()))?;
+    }
+
+    app.theme_directory = path.to_string_lossy().to_string();
+    app.pending_create_path = None;
+    Ok(true)
+}
+
+/// Walks the same sources as `create_theme` without creating or copying
+/// anything.
+fn dry_run(app: &App) -> Vec<DryRunEntry> {
+    let mut entries = Vec::new();
+
+    for comp in app.checked_components() {
+        let exclude = app.exclude_set(comp);
+        for source in comp.export_plan() {
+            let size = source.path.exists().then(|| source.scan(&exclude));
+            entries.push(DryRunEntry {
+                component: comp.name.clone(),
+                path: source.path,
+                size,
+            });
+        }
+    }
+
+    entries
+}
+
+/// Compares a previous export in `theme_dir` with what `create_theme` would
+/// write now for the checked components. Folders of other components in the
+/// old export are left out.
+/// Hashing can take a while for large themes, so each compared file is
+/// reported as progress and the comparison can be cancelled.
+fn diff_export(app: &App, theme_dir: &Path, reporter: &mut dyn Reporter) -> Result<Vec<DiffEntry>> {
+    if !theme_dir.join("theme_info.txt").is_file() {
+        anyhow::bail!("{} is not an exported theme", theme_dir.display());
+    }
+
+    let planned = planned_files(app);
+    let mut previous = std::collections::BTreeMap::new();
+    for comp in app.checked_components() {
+        let dir = std::path::PathBuf::from(comp.dir_name());
+        collect_files(
+            &theme_dir.join(&dir),
+            dir,
+            &GlobSet::empty(),
+            false,
+            &mut previous,
+        );
+    }
+
+    let mut progress = CopyProgress {
+        overall_total: planned.len() as u64,
+        ..Default::default()
+    };
+    progress.start_component("Comparing", planned.len() as u64);
+    let mut report = Vec::new();
+    for (path, source) in &planned {
+        progress.advance();
+        reporter.progress(&progress);
+        check_cancelled(reporter)?;
+        let change = match previous.get(path) {
+            None => Some(Change::Added),
+            Some(old) if !same_content(source, old) => Some(Change::Modified),
+            Some(_) => None,
+        };
+        if let Some(change) = change {
+            report.push(DiffEntry {
+                change,
+                path: path.clone(),
+            });
+        }
+    }
+    for path in previous.keys().filter(|path| !planned.contains_key(*path)) {
+        report.push(DiffEntry {
+            change: Change::Removed,
+            path: path.clone(),
+        });
+    }
+    report.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(report)
+}
+
+/// Every file `create_theme` would write for the checked components, keyed by
+/// its path inside the theme folder, with the file it is copied from.
+fn planned_files(app: &App) -> std::collections::BTreeMap<std::path::PathBuf, std::path::PathBuf> {
+    let mut files = std::collections::BTreeMap::new();
+    for comp in app.checked_components() {
+        let exclude = app.exclude_set(comp);
+        for source in comp.export_plan() {
+            source.collect_files(&exclude, &mut files);
+        }
+    }
+    files
+}
+
+/// Adds the files and links under `source` to `files`, keyed by where they
+/// sit below `export`. Like `copy_as`, only `source` itself is followed
+/// when `follow` is set.
+fn collect_files(
+    source: &Path,
+    export: std::path::PathBuf,
+    exclude: &GlobSet,
+    follow: bool,
+    files: &mut std::collections::BTreeMap<std::path::PathBuf, std::path::PathBuf>,
+) {
+    let metadata = if follow {
+        fs::metadata(source)
+    } else {
+        fs::symlink_metadata(source)
+    };
+    let Ok(metadata) = metadata else {
+        return;
+    };
+
+    if metadata.is_dir() {
+        let Ok(entries) = fs::read_dir(source) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            if !exclude.is_match(entry.file_name()) {
+                collect_files(
+                    &entry.path(),
+                    export.join(entry.file_name()),
+                    exclude,
+                    false,
+                    files,
+                );
+            }
+        }
+    } else {
+        files.insert(export, source.to_path_buf());
+    }
+}
+
+/// Whether two files (or links) have the same content. Unreadable files count
+/// as different.
+fn same_content(a: &Path, b: &Path) -> bool {
+    let (Ok(meta_a), Ok(meta_b)) = (fs::metadata(a), fs::symlink_metadata(b)) else {
+        return false;
+    };
+    if meta_b.is_symlink() {
+        return fs::read_link(a)
+            .ok()
+            .is_some_and(|target| fs::read_link(b).ok() == Some(target));
+    }
+    if meta_a.len() != meta_b.len() {
+        return false;
+    }
+    match (file_hash(a), file_hash(b)) {
+        (Ok(hash_a), Ok(hash_b)) => hash_a == hash_b,
+        _ => false,
+    }
+}
+
+/// Replaces every file under `dir` whose content and mode match an earlier
+/// one by a hardlink to it. Returns how many files were linked and the bytes
+/// that saves.
+fn dedupe_files(dir: &Path) -> io::Result<(usize, u64)> {
+    let mut files = Vec::new();
+    regular_files(dir, &mut files);
+
+    // Only files of the same size can match, which spares most of the hashing
+    let mut groups: std::collections::BTreeMap<(u64, u32), Vec<_>> =
+        std::collections::BTreeMap::new();
+    for (path, metadata) in files {
+        if metadata.len() > 0 {
+            groups
+                .entry((metadata.len(), metadata.mode()))
+                .or_default()
+                .push((path, metadata.ino()));
+        }
+    }
+
+    let (mut linked, mut saved) = (0, 0);
+    for ((size, _), group) in groups.into_iter().filter(|(_, group)| group.len() > 1) {
+        let mut originals: std::collections::HashMap<Vec<u8>, (std::path::PathBuf, u64)> =
+            std::collections::HashMap::new();
+        for (path, inode) in group {
+            let hash = file_hash(&path)?;
+            match originals.get(&hash) {
+                // Already a link to it, e.g. from an earlier export
+                Some((_, original_inode)) if *original_inode == inode => {}
+                Some((original, _)) => {
+                    // Linked beside the duplicate first, so it is never lost
+                    let temporary = path.with_file_name(format!(
+                        ".{}.kde-copycat",
+                        path.file_name().unwrap_or_default().to_string_lossy()
+                    ));
+                    fs::hard_link(original, &temporary)?;
+                    fs::rename(&temporary, &path)?;
+                    linked += 1;
+                    saved += size;
+                }
+                None => {
+                    originals.insert(hash, (path, inode));
+                }
+            }
+        }
+    }
+    Ok((linked, saved))
+}
+
+/// Regular files under `dir`, in directory order, without following links.
+fn regular_files(dir: &Path, files: &mut Vec<(std::path::PathBuf, fs::Metadata)>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut entries: Vec<_> = entries.flatten().collect();
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if metadata.is_dir() {
+            regular_files(&entry.path(), files);
+        } else if metadata.is_file() {
+            files.push((entry.path(), metadata));
+        }
+    }
+}
+
+fn file_hash(path: &Path) -> io::Result<Vec<u8>> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+    Ok(hasher.finalize().to_vec())
+}
+
+fn print_diff(report: &[DiffEntry]) {
+    for entry in report {
+        let marker = match entry.change {
+            Change::Added => '+',
+            Change::Removed => '-',
+            Change::Modified => '~',
+        };
+        println!("{} {}", marker, entry.path.display());
+    }
+    let count = |change| report.iter().filter(|entry| entry.change == change).count();
+    println!(
+        "{} added, {} removed, {} modified",
+        count(Change::Added),
+        count(Change::Removed),
+        count(Change::Modified)
+    );
+}
+
+/// Total size of everything `create_theme` would copy.
+fn estimate_size(app: &App) -> u64 {
+    dry_run(app)
+        .iter()
+        .filter_map(|entry| entry.size)
+        .map(|(_, bytes)| bytes)
+        .sum()
+}
+
+fn print_dry_run(entries: &[DryRunEntry]) {
+    let (mut total_files, mut total_bytes) = (0, 0);
+    let mut current_component = "";
+
+    println!("Dry run: nothing will be written.");
+    for entry in entries {
+        if entry.component != current_component {
+            current_component = &entry.component;
+            println!();
+            println!("{}", current_component);
+        }
+        match entry.size {
+            Some((files, bytes)) => {
+                total_files += files;
+                total_bytes += bytes;
+                println!(
+                    "  {} ({} files, {})",
+                    entry.path.display(),
+                    files,
+                    format_size(bytes)
+                );
+            }
+            None => println!("  {} (not found)", entry.path.display()),
+        }
+    }
+    println!();
+    println!("Total: {} files, {}", total_files, format_size(total_bytes));
+}
+
+/// Exports the checked components and returns what was copied and skipped.
+fn create_theme(app: &App, reporter: &mut dyn Reporter) -> Result<ExportResult> {
+    check_theme_location(app)?;
+    let theme_dir = std::path::Path::new(&app.theme_directory).join(&app.theme_name);
+
+    // Ensure we have absolute path for display
+    let display_theme_dir = if theme_dir.is_absolute() {
+        theme_dir.clone()
+    } else {
+        std::env::current_dir()
+            .context("Failed to get current directory")?
+            .join(&theme_dir)
+    };
+
+    // A cancelled export only removes the folder if this run created it
+    let existed = display_theme_dir.exists();
+    fs::create_dir_all(&display_theme_dir)?;
+    log::info!(
+        "Creating theme {} in {}",
+        app.theme_name,
+        display_theme_dir.display()
+    );
+
+    let mut copied_files = Vec::new();
+    let mut skipped_files = Vec::new();
+    // Every file written, as (source, destination), for `verify_copy`
+    let mut copied_pairs = Vec::new();
+    // Home folders now live in the theme, so it must survive a cancel
+    let mut linked_any = false;
+    // Unreadable entries left for `sudo_copy`, as (component, source, destination)
+    let mut denied = Vec::new();
+    // Cleared by "skip all" at a failure prompt
+    let mut ask_on_failure = true;
+    let created = chrono::Utc::now();
+    let mut manifest = Manifest {
+        theme_name: app.theme_name.clone(),
+        created: created.to_rfc3339(),
+        color_scheme: exports_color_scheme(app)
+            .then(color_preference)
+            .flatten()
+            .map(|(scheme, _)| scheme),
+        components: Vec::new(),
+    };
+
+    // Show user what we're doing
+    reporter.log(&format!("{} Scanning for theme files...", glyphs().busy));
+    reporter.log("");
+
+    // Count files up front so progress can be reported against a total
+    let mut progress = CopyProgress::default();
+    let mut plans = Vec::new();
+    for comp in app.checked_components() {
+        let exclude = app.exclude_set(comp);
+        let plan = comp.export_plan();
+        let total: u64 = plan.iter().map(|source| source.scan(&exclude).0).sum();
+        progress.overall_total += total;
+        plans.push((comp, plan, total));
+    }
+
+    for (comp, plan, total) in plans {
+        let component_dir = display_theme_dir.join(comp.dir_name());
+        fs::create_dir_all(&component_dir)?;
+
+        reporter.log(&format!("{} Processing: {}", glyphs().folder, comp.name));
+        let exclude = app.exclude_set(comp);
+        progress.start_component(&comp.name, total);
+        reporter.progress(&progress);
+        let mut manifest_component = ManifestComponent {
+            name: comp.name.clone(),
+            description: comp.description.clone(),
+            source_paths: Vec::new(),
+            files: Vec::new(),
+            slideshow: Vec::new(),
+        };
+        if comp.name == "Wallpapers" {
+            manifest_component.slideshow = wallpaper_slideshow_paths()
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect();
+        }
+
+        for source in &plan {
+            let path = &source.path;
+            let export = display_theme_dir.join(&source.destination);
+            match &source.configured {
+                Some(configured) => {
+                    reporter.log(&format!(
+                        "   Checking: {} -> {}",
+                        configured,
+                        path.display()
+                    ));
+                    manifest_component
+                        .source_paths
+                        .push(path.display().to_string());
+                }
+                None => reporter.log(&format!("   Referenced: {}", path.display())),
+            }
+
+            let is_source = source.configured.is_some();
+            if is_source && path.exists() && app.link_sources && comp.can_link(path, &exclude) {
+                let files = count_files(path, &exclude);
+                match move_and_link(path, &export) {
+                    Ok(()) => {
+                        linked_any = true;
+                        (0..files).for_each(|_| progress.advance());
+                        reporter.progress(&progress);
+                        log::info!("{}: moved {} and linked it back", comp.name, path.display());
+                        copied_files.push(format!("{}: {} (linked)", comp.name, path.display()));
+                        manifest_component.files.push(ManifestFile::linked(path));
+                        reporter.log(&format!(
+                            "   {} Moved into the theme and linked back",
+                            glyphs().check
+                        ));
+                    }
+                    Err(e) => {
+                        log::warn!("{}: failed to move {}: {:#}", comp.name, path.display(), e);
+                        reporter.log(&format!("   {} Failed to move: {}", glyphs().cross, e));
+                        skipped_files.push(format!("{}: {} ({})", comp.name, path.display(), e));
+                        manifest_component
+                            .files
+                            .push(ManifestFile::skipped(path, &e));
+                    }
+                }
+            } else if path.exists() || !is_source {
+                let result = source.copy_with_retry(
+                    &display_theme_dir,
+                    &exclude,
+                    reporter,
+                    &mut ask_on_failure,
+                    app.sudo_unreadable,
+                    &mut |from, to, reporter| {
+                        copied_pairs.push((from.to_path_buf(), to.to_path_buf()));
+                        progress.advance();
+                        reporter.progress(&progress);
+                        check_cancelled(reporter)
+                    },
+                );
+                match result {
+                    Err(e) if e.is::<Cancelled>() => {
+                        return abandon_export(&display_theme_dir, existed, linked_any, reporter);
+                    }
+                    Ok(skipped) => {
+                        for (skipped_path, e) in skipped {
+                            if app.sudo_unreadable && permission_denied(&e) {
+                                let destination = copy_destination(path, &skipped_path, &export);
+                                denied.push((manifest.components.len(), skipped_path, destination));
+                                continue;
+                            }
+                            reporter.log(&format!(
+                                "   {} Skipped {}: {}",
+                                glyphs().warning,
+                                skipped_path.display(),
+                                e
+                            ));
+                            skipped_files.push(format!(
+                                "{}: {} ({})",
+                                comp.name,
+                                skipped_path.display(),
+                                e
+                            ));
+                            manifest_component
+                                .files
+                                .push(ManifestFile::skipped(&skipped_path, &e));
+                        }
+                        if is_source {
+                            log::info!("{}: copied {}", comp.name, path.display());
+                        } else {
+                            log::info!("{}: copied referenced {}", comp.name, path.display());
+                        }
+                        copied_files.push(format!("{}: {}", comp.name, path.display()));
+                        manifest_component.files.push(ManifestFile::copied(path));
+                        reporter.log(&format!("   {} Successfully copied", glyphs().check));
+                    }
+                    Err(e) if app.sudo_unreadable && permission_denied(&e) => {
+                        denied.push((manifest.components.len(), path.clone(), export.clone()));
+                    }
+                    Err(e) => {
+                        log::warn!("{}: failed to copy {}: {:#}", comp.name, path.display(), e);
+                        reporter.log(&format!("   {} Failed to copy: {}", glyphs().cross, e));
+                        skipped_files.push(format!("{}: {} ({})", comp.name, path.display(), e));
+                        manifest_component
+                            .files
+                            .push(ManifestFile::skipped(path, &e));
+                    }
+                }
+            } else {
+                log::info!("{}: skipped {}, not found", comp.name, path.display());
+                reporter.log(&format!("   {} Path not found", glyphs().warning));
+                skipped_files.push(format!("{}: {} (not found)", comp.name, path.display()));
+                manifest_component
+                    .files
+                    .push(ManifestFile::skipped(path, "not found"));
+            }
+        }
+        manifest.components.push(manifest_component);
+        reporter.log("");
+    }
+
+    // Not readable for `verify_copy`, so only counted
+    let mut sudo_copied = 0;
+    if !denied.is_empty() {
+        reporter.log(&format!(
+            "{} Copying {} unreadable paths with sudo...",
+            glyphs().busy,
+            denied.len()
+        ));
+        let owner = fs::metadata(&display_theme_dir)?;
+        // Ask for the password once, the copies then run with `sudo -n`
+        let authorized = reporter
+            .run_interactive(Command::new("sudo").arg("-v"))
+            .is_ok_and(|status| status.success());
+        for (index, source, destination) in denied {
+            let component = &mut manifest.components[index];
+            let result = if authorized {
+                sudo_copy(&source, &destination, owner.uid(), owner.gid())
+            } else {
+                Err(anyhow::anyhow!("sudo was not authorized"))
+            };
+            match result {
+                Ok(()) => {
+                    sudo_copied += 1;
+                    log::info!("{}: copied {} with sudo", component.name, source.display());
+                    copied_files.push(format!("{}: {} (sudo)", component.name, source.display()));
+                    component.files.push(ManifestFile::copied(&source));
+                    reporter.log(&format!(
+                        "   {} Copied with sudo: {}",
+                        glyphs().check,
+                        source.display()
+                    ));
+                }
+                Err(e) => {
+                    log::warn!(
+                        "{}: failed to copy {}: {:#}",
+                        component.name,
+                        source.display(),
+                        e
+                    );
+                    reporter.log(&format!(
+                        "   {} Failed to copy {}: {}",
+                        glyphs().cross,
+                        source.display(),
+                        e
+                    ));
+                    skipped_files.push(format!("{}: {} ({})", component.name, source.display(), e));
+                    component.files.push(ManifestFile::skipped(&source, &e));
+                }
+            }
+        }
+        reporter.log("");
+    }
+
+    let verification = if app.verify {
+        reporter.log(&format!("{} Verifying copied files...", glyphs().busy));
+        let failures = verify_copy(&copied_pairs);
+        for failure in &failures {
+            log::warn!("Verification failed: {}", failure);
+        }
+        if sudo_copied > 0 {
+            reporter.log(&format!(
+                "   {} paths copied with sudo are not verified",
+                sudo_copied
+            ));
+        }
+        Some(failures)
+    } else {
+        None
+    };
+
+    // Moved-in home files are the user's live configuration, so they are
+    // never tied together
+    let deduplicated = if app.dedupe && !linked_any {
+        reporter.log(&format!("{} Hardlinking identical files...", glyphs().busy));
+        let (mut linked, mut saved) = (0, 0);
+        for comp in app.checked_components() {
+            let component_dir = display_theme_dir.join(comp.dir_name());
+            match dedupe_files(&component_dir) {
+                Ok((files, bytes)) => {
+                    log::info!("{}: hardlinked {} identical files", comp.name, files);
+                    linked += files;
+                    saved += bytes;
+                }
+                Err(e) => log::warn!("{}: deduplication stopped: {}", comp.name, e),
+            }
+        }
+        Some((linked, saved))
+    } else {
+        None
+    };
+
+    // Create theme metadata
+    let metadata_file = display_theme_dir.join("theme_info.txt");
+    let mut metadata_content = format!(
+        "Theme Name: {}\nCreated: {}\nSaved at: {}\nComponents:\n{}\n\nSuccessfully copied files:\n{}\n\nSkipped files:\n{}\n\nRuntime info:\n- USER: {}\n- HOME: {}\n- SUDO_USER: {}\n- Plasma: {}\n- Session: {}\n",
+        app.theme_name,
+        created.format("%Y-%m-%d %H:%M:%S UTC"),
+        display_theme_dir.display(),
+        app.checked_components()
+            .iter()
+            .map(|c| format!("- {}: {}", c.name, c.description))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        if copied_files.is_empty() {
+            "No files were copied".to_string()
+        } else {
+            copied_files.iter().map(|f| format!("- {}", f)).collect::<Vec<_>>().join("\n")
+        },
+        if skipped_files.is_empty() {
+            "No files were skipped".to_string()
+        } else {
+            skipped_files.iter().map(|f| format!("- {}", f)).collect::<Vec<_>>().join("\n")
+        },
+        std::env::var("USER").unwrap_or_else(|_| "unknown".to_string()),
+        std::env::var("HOME").unwrap_or_else(|_| "unknown".to_string()),
+        std::env::var("SUDO_USER").unwrap_or_else(|_| "not set".to_string()),
+        plasma_version().unwrap_or("not found"),
+        session_type().label().unwrap_or("unknown"),
+    );
+    if let Some(failures) = &verification {
+        metadata_content.push_str(&format!(
+            "\nVerification ({} files):\n{}\n",
+            copied_pairs.len(),
+            if failures.is_empty() {
+                "All files match their source".to_string()
+            } else {
+                failures
+                    .iter()
+                    .map(|f| format!("- {}", f))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+        ));
+    }
+    if let Some((linked, saved)) = deduplicated {
+        metadata_content.push_str(&format!(
+            "\nDeduplication: {} identical files hardlinked, {} saved\n",
+            linked,
+            format_size(saved)
+        ));
+    }
+    fs::write(metadata_file, metadata_content)?;
+
+    let manifest_file = display_theme_dir.join(app.manifest_format.file_name());
+    fs::write(manifest_file, app.manifest_format.serialize(&manifest)?)?;
+    // A re-export in another format would otherwise leave the old manifest
+    for format in ManifestFormat::ALL
+        .into_iter()
+        .filter(|f| *f != app.manifest_format)
+    {
+        let _ = fs::remove_file(display_theme_dir.join(format.file_name()));
+    }
+
+    let install_file = display_theme_dir.join("install.sh");
+    fs::write(&install_file, install_script(app, &display_theme_dir))?;
+    fs::set_permissions(&install_file, fs::Permissions::from_mode(0o755))?;
+
+    // Show success message
+    reporter.log(&"=".repeat(60));
+    reporter.log(&format!(
+        "{} THEME CREATION COMPLETE! {}",
+        glyphs().done,
+        glyphs().done
+    ));
+    reporter.log(&"=".repeat(60));
+    reporter.log(&format!("Theme Name: {}", app.theme_name));
+    reporter.log(&format!("Saved at: {}", display_theme_dir.display()));
+    reporter.log(&format!(
+        "Components included: {}",
+        app.checked_components().len()
+    ));
+    reporter.log(&format!(
+        "Files successfully copied: {}",
+        copied_files.len()
+    ));
+    if !skipped_files.is_empty() {
+        reporter.log(&format!("Files skipped/not found: {}", skipped_files.len()));
+    }
+    match &verification {
+        Some(failures) if failures.is_empty() => reporter.log(&format!(
+            "Verification: all {} files match their source",
+            copied_pairs.len()
+        )),
+        Some(failures) => {
+            reporter.log(&format!(
+                "{} {} files failed verification:",
+                glyphs().warning,
+                failures.len()
+            ));
+            for failure in failures {
+                reporter.log(&format!("   {}", failure));
+            }
+        }
+        None => {}
+    }
+    if let Some((linked, saved)) = deduplicated {
+        reporter.log(&format!(
+            "Identical files hardlinked: {} ({} saved)",
+            linked,
+            format_size(saved)
+        ));
+    }
+    reporter.log(&"=".repeat(60));
+    reporter.log(&format!(
+        "You can find your theme at: {}",
+        display_theme_dir.display()
+    ));
+    reporter.log(&format!(
+        "A theme_info.txt file and {} have been created with complete details.",
+        app.manifest_format.file_name()
+    ));
+    reporter.log("Run install.sh inside it to install the theme without kde-copycat.");
+    reporter.log(&format!("Details of this run: {}", log_path().display()));
+    let mut created_paths = vec![display_theme_dir.clone()];
+    if app.create_archive {
+        let archive = create_archive(&display_theme_dir)?;
+        reporter.log(&format!("Archive: {}", archive.display()));
+        created_paths.push(archive);
+    }
+    if app.git_add {
+        match git_add(&created_paths) {
+            Ok(Some(repository)) => {
+                reporter.log(&format!("Staged in git: {}", repository.display()))
+            }
+            Ok(None) => reporter.log("Not inside a git repository, nothing was staged"),
+            Err(e) => {
+                log::warn!("{:#}", e);
+                reporter.log(&format!("{} {:#}", glyphs().warning, e));
+            }
+        }
+    }
+    if copied_files.is_empty() {
+        reporter.log("");
+        reporter.log(&format!(
+            "{} Warning: No files were copied. Check the paths and permissions.",
+            glyphs().warning
+        ));
+        reporter.log("The app might be looking for files in the wrong home directory.");
+    }
+    reporter.log(&"=".repeat(60));
+
+    Ok(ExportResult {
+        theme_dir: display_theme_dir,
+        copied: copied_files,
+        skipped: skipped_files,
+        verify_failures: verification.unwrap_or_default(),
+    })
+}
+
+/// Runs `git add` on `paths` in the work tree the first one is in, found by
+/// looking for `.git` (a folder, or a file in worktrees and submodules) in
+/// its parents. Returns the work tree, or `None` outside of one. Under sudo
+/// git runs as the invoking user, so the repository doesn't get root-owned
+/// objects.
+fn git_add(paths: &[std::path::PathBuf]) -> Result<Option<std::path::PathBuf>> {
+    let Some(repository) = paths
+        .first()
+        .and_then(|path| path.ancestors().find(|dir| dir.join(".git").exists()))
+    else {
+        return Ok(None);
+    };
+
+    let mut command = match env::var("SUDO_USER") {
+        Ok(user) if effective_uid() == Some(0) => {
+            let mut command = Command::new("sudo");
+            command.args(["-n", "-u", &user, "git"]);
+            command
+        }
+        _ => Command::new("git"),
+    };
+    let output = command
+        .arg("-C")
+        .arg(repository)
+        .args(["add", "--"])
+        .args(paths)
+        .output()
+        .context("Failed to run git")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "git add failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    log::info!("Staged {} in {}", paths[0].display(), repository.display());
+    Ok(Some(repository.to_path_buf()))
+}
+
+/// Whether `e` comes from an entry the current user may not read.
+fn permission_denied(e: &anyhow::Error) -> bool {
+    e.chain().any(|cause| {
+        cause
+            .downcast_ref::<io::Error>()
+            .is_some_and(|e| e.kind() == io::ErrorKind::PermissionDenied)
+    })
+}
+
+/// Asks the reporter about each entry that failed to copy, copying it again
+/// with `copy` on retry, and returns the entries left skipped. Unreadable
+/// entries are not asked about when `sudo_unreadable` copies them later.
+/// Fails with `Cancelled` when the user aborts the export.
+fn retry_failed<F>(
+    skipped: Vec<SkippedEntry>,
+    reporter: &mut dyn Reporter,
+    ask: &mut bool,
+    sudo_unreadable: bool,
+    mut copy: F,
+) -> Result<Vec<SkippedEntry>>
+where
+    F: FnMut(&Path, &mut dyn Reporter) -> Result<Vec<SkippedEntry>>,
+{
+    let mut pending: std::collections::VecDeque<SkippedEntry> = skipped.into();
+    let mut left = Vec::new();
+    while let Some((path, e)) = pending.pop_front() {
+        if !*ask || (sudo_unreadable && permission_denied(&e)) {
+            left.push((path, e));
+            continue;
+        }
+        match reporter.copy_failed(&path, &e) {
+            FailureAction::Retry => match copy(&path, reporter) {
+                Ok(still_failing) => {
+                    for entry in still_failing.into_iter().rev() {
+                        pending.push_front(entry);
+                    }
+                }
+                Err(e) if e.is::<Cancelled>() => return Err(e),
+                Err(e) => pending.push_front((path, e)),
+            },
+            FailureAction::Skip => left.push((path, e)),
+            FailureAction::SkipAll => {
+                *ask = false;
+                left.push((path, e));
+            }
+            FailureAction::Abort => return Err(Cancelled.into()),
+        }
+    }
+    Ok(left)
+}
+
+/// Where `entry`, a path inside `root` (or `root` itself), ends up when
+/// `root` is copied to `root_destination`.
+fn copy_destination(root: &Path, entry: &Path, root_destination: &Path) -> std::path::PathBuf {
+    match entry.strip_prefix(root) {
+        Ok(relative) if relative.as_os_str().is_empty() => root_destination.to_path_buf(),
+        Ok(relative) => root_destination.join(relative),
+        Err(_) => root_destination.join(entry.file_name().unwrap_or_default()),
+    }
+}
+
+/// Copies one entry the user can't read with `sudo cp`, then hands the copy
+/// to `uid`/`gid` so the theme stays editable. Expects `sudo -v` to have
+/// been run so no password prompt is needed.
+fn sudo_copy(source: &Path, destination: &Path, uid: u32, gid: u32) -> Result<()> {
+    let parent = destination.parent().context("Invalid path name")?;
+    fs::create_dir_all(parent)?;
+
+    let mut copy = Command::new("sudo");
+    copy.args(["-n", "cp", "-aT", "--"])
+        .arg(source)
+        .arg(destination);
+    let mut chown = Command::new("sudo");
+    chown
+        .args(["-n", "chown", "-R", &format!("{}:{}", uid, gid), "--"])
+        .arg(destination);
+    for mut command in [copy, chown] {
+        let output = command.output().context("Failed to run sudo")?;
+        if !output.status.success() {
+            anyhow::bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+        }
+    }
+    Ok(())
+}
+
+/// Returned through `copy_as` when the user cancels an export.
+#[derive(Debug)]
+struct Cancelled;
+
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Export cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+fn check_cancelled(reporter: &mut dyn Reporter) -> Result<()> {
+    if reporter.cancelled() {
+        Err(Cancelled.into())
+    } else {
+        Ok(())
+    }
+}
+
+/// Cleans up after a cancelled `create_theme`. The partial theme folder is
+/// removed unless it held an earlier export or linked home files.
+fn abandon_export<T>(
+    theme_dir: &Path,
+    existed: bool,
+    linked_any: bool,
+    reporter: &mut dyn Reporter,
+) -> Result<T> {
+    log::info!("Export cancelled");
+    let outcome = if existed {
+        format!(
+            "Export cancelled, {} was partly updated",
+            theme_dir.display()
+        )
+    } else if linked_any {
+        format!(
+            "Export cancelled, {} was kept because home files link into it",
+            theme_dir.display()
+        )
+    } else {
+        fs::remove_dir_all(theme_dir)
+            .with_context(|| format!("Failed to remove {}", theme_dir.display()))?;
+        format!("Export cancelled, removed {}", theme_dir.display())
+    };
+    reporter.log(&outcome);
+    Err(anyhow::Error::new(Cancelled).context(outcome))
+}
+
+/// Re-reads every copied (source, destination) pair and describes the ones
+/// that don't match: a missing destination, a different size, or a link
+/// pointing elsewhere. Catches copies truncated by a full disk.
+///
+/// Links are compared by target only where the copy is a link: a source
+/// path that is itself a link (a stow-managed kwinrc) is followed and
+/// copied as a regular file, so it is compared with the file it points to.
+/// Entries copied with `sudo_copy` are not in `copied` and aren't verified.
+fn verify_copy(copied: &[(std::path::PathBuf, std::path::PathBuf)]) -> Vec<String> {
+    let mut failures = Vec::new();
+    for (source, destination) in copied {
+        let dest_metadata = match fs::symlink_metadata(destination) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                failures.push(format!("{}: {}", destination.display(), e));
+                continue;
+            }
+        };
+        let is_link = dest_metadata.file_type().is_symlink();
+        let source_metadata = if is_link {
+            fs::symlink_metadata(source)
+        } else {
+            fs::metadata(source)
+        };
+        let Ok(source_metadata) = source_metadata else {
+            // Gone since it was copied, there is nothing left to compare
+            continue;
+        };
+
+        if is_link {
+            if fs::read_link(source).ok() != fs::read_link(destination).ok() {
+                failures.push(format!("{}: link target differs", destination.display()));
+            }
+        } else if source_metadata.len() != dest_metadata.len() {
+            failures.push(format!(
+                "{}: {} instead of {}",
+                destination.display(),
+                format_size(dest_metadata.len()),
+                format_size(source_metadata.len())
+            ));
+        }
+    }
+    failures
+}
+
+/// Shell script that copies the exported folders back where they came from,
+/// for recipients who don't have kde-copycat. Destinations are chosen the same
+/// way as `plan_restore`.
+fn install_script(app: &App, theme_dir: &Path) -> String {
+    let mut script = format!(
+        r#"#!/bin/sh
+# Installs the "{}" theme exported by kde-copycat.
+set -e
+cd "$(dirname "$0")"
+
+# install_entry SOURCE DEST: copies SOURCE to DEST (merging into an existing
+# folder), using sudo when the target folder isn't writable.
+install_entry() {{
+    parent=$(dirname "$2")
+    if mkdir -p "$parent" 2>/dev/null && [ -w "$parent" ]; then
+        cp -aT "$1" "$2"
+    else
+        sudo mkdir -p "$parent"
+        sudo cp -aT "$1" "$2"
+    fi
+    echo "Installed $2"
+}}
+"#,
+        app.theme_name.replace(['"', '\n'], "")
+    );
+
+    for comp in app.checked_components() {
+        let component_dir = theme_dir.join(comp.dir_name());
+        let Ok(entries) = fs::read_dir(&component_dir) else {
+            continue;
+        };
+        let mut lines = Vec::new();
+        for entry in entries.flatten() {
+            let entry_name = entry.file_name();
+            let Some(destination) = comp.restore_destination(&entry.path()) else {
+                continue;
+            };
+            let source = format!("{}/{}", comp.dir_name(), entry_name.to_string_lossy());
+            lines.push(format!(
+                "install_entry {} {}",
+                shell_quote(&source),
+                shell_path(&destination)
+            ));
+        }
+
+        if !lines.is_empty() {
+            lines.sort();
+            script.push_str(&format!("\n# {}\n", comp.name));
+            for line in lines {
+                script.push_str(&line);
+                script.push('\n');
+            }
+        }
+    }
+
+    if let Some((scheme, _)) = exports_color_scheme(app).then(color_preference).flatten() {
+        script.push_str(&format!(
+            "\n# Dark or light preference\nif command -v gsettings >/dev/null 2>&1; then\n    gsettings set org.gnome.desktop.interface color-scheme 'prefer-{}' || true\nfi\n",
+            scheme
+        ));
+    }
+
+    script
+}
+
+/// Whether the export includes a component the dark/light preference
+/// belongs to.
+fn exports_color_scheme(app: &App) -> bool {
+    app.checked_components()
+        .iter()
+        .any(|comp| comp.name == "GTK Themes" || comp.name == "Application Style")
+}
+
+/// Quotes `value` for a POSIX shell.
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// Quotes a path for a POSIX shell, keeping a leading `~` as `$HOME` (and the
+/// XDG directories as their variables) so the script installs into the home
+/// of whoever runs it.
+fn shell_path(path: &str) -> String {
+    for (prefix, base) in [
+        ("~/.config", "${XDG_CONFIG_HOME:-$HOME/.config}"),
+        ("~/.local/share", "${XDG_DATA_HOME:-$HOME/.local/share}"),
+        ("~", "$HOME"),
+    ] {
+        match path.strip_prefix(prefix) {
+            Some("") => return format!("\"{}\"", base),
+            Some(rest) if rest.starts_with('/') => {
+                return format!("\"{}\"/{}", base, shell_quote(&rest[1..]));
+            }
+            _ => {}
+        }
+    }
+    shell_quote(path)
+}
+
+/// Bundles `theme_dir` into `<theme_name>.tar.gz` next to it. Entries are
+/// stored relative to the parent so the archive unpacks into a single folder.
+fn create_archive(theme_dir: &Path) -> Result<std::path::PathBuf> {
+    let dir_name = theme_dir
+        .file_name()
+        .context("Invalid theme directory name")?;
+    let archive_path = theme_dir.with_file_name(format!("{}.tar.gz", dir_name.to_string_lossy()));
+
+    let file = fs::File::create(&archive_path)
+        .with_context(|| format!("Failed to create {}", archive_path.display()))?;
+    let encoder = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+    let mut builder = tar::Builder::new(encoder);
+    builder.follow_symlinks(false);
+    builder
+        .append_dir_all(dir_name, theme_dir)
+        .with_context(|| format!("Failed to archive {}", theme_dir.display()))?;
+    builder.into_inner()?.finish()?;
+
+    Ok(archive_path)
+}
+
+fn check_permissions(app: &App) -> Vec<PermissionIssue> {
+    let mut issues = Vec::new();
+
+    for component in app.checked_components() {
+        for path_str in component.active_sources() {
+            let path = expand_path(path_str);
+
+            if path.exists() {
+                // Check read permissions
+                if !path.readable() {
+                    issues.push(PermissionIssue {
+                        component: component.name.clone(),
+                        path: path.display().to_string(),
+                        issue_type: PermissionIssueType::NoReadAccess,
+                    });
+                }
+
+                // Check if we need sudo for system directories
+                if path.starts_with("/usr") || path.starts_with("/etc") {
+                    // Try to create a temp file to test write access
+                    let test_file = path.join(".theme_creator_test");
+                    if fs::write(&test_file, "test").is_err() {
+                        let _ = fs::remove_file(&test_file); // Clean up if it was created
+                        issues.push(PermissionIssue {
+                            component: component.name.clone(),
+                            path: path.display().to_string(),
+                            issue_type: PermissionIssueType::SudoRequired,
+                        });
+                    } else {
+                        let _ = fs::remove_file(&test_file); // Clean up test file
+                    }
+                }
+            }
+        }
+    }
+
+    for issue in &issues {
+        log::warn!(
+            "{}: {} ({})",
+            issue.component,
+            issue.path,
+            issue.issue_type.description()
+        );
+    }
+    issues
+}
+
+fn generate_chmod_commands(issues: &[PermissionIssue]) -> String {
+    let mut commands = Vec::new();
+    let mut processed_paths = std::collections::HashSet::new();
+
+    for issue in issues {
+        let path = &issue.path;
+        if !processed_paths.contains(path) {
+            if path.starts_with("/usr") || path.starts_with("/etc") {
+                commands.push(format!("sudo chmod -R 755 \"{}\"", path));
+            } else {
+                commands.push(format!("chmod -R 755 \"{}\"", path));
+            }
+            processed_paths.insert(path.clone());
+        }
+    }
+
+    if commands.is_empty() {
+        "No chmod commands needed".to_string()
+    } else {
+        commands.join("\n")
+    }
+}
+
+fn copy_to_clipboard(text: &str) -> Result<()> {
+    // Try xclip first (most common)
+    if Command::new("xclip")
+        .arg("-selection")
+        .arg("clipboard")
+        .stdin(std::process::Stdio::piped())
+        .spawn()
+        .and_then(|mut child| {
+            if let Some(stdin) = child.stdin.as_mut() {
+                use std::io::Write;
+                stdin.write_all(text.as_bytes())?;
+            }
+            child.wait()
+        })
+        .is_ok()
+    {
+        return Ok(());
+    }
+
+    // Try wl-copy (Wayland)
+    if Command::new("wl-copy")
+        .stdin(std::process::Stdio::piped())
+        .spawn()
+        .and_then(|mut child| {
+            if let Some(stdin) = child.stdin.as_mut() {
+                use std::io::Write;
+                stdin.write_all(text.as_bytes())?;
+            }
+            child.wait()
+        })
+        .is_ok()
+    {
+        return Ok(());
+    }
+
+    // Try xsel (alternative)
+    if Command::new("xsel")
+        .arg("--clipboard")
+        .arg("--input")
+        .stdin(std::process::Stdio::piped())
+        .spawn()
+        .and_then(|mut child| {
+            if let Some(stdin) = child.stdin.as_mut() {
+                use std::io::Write;
+                stdin.write_all(text.as_bytes())?;
+            }
+            child.wait()
+        })
+        .is_ok()
+    {
+        return Ok(());
+    }
+
+    Err(anyhow::anyhow!("No clipboard utility found"))
+}
+
+/// Refuses a theme folder inside one of the checked sources, or a source
+/// inside the theme folder, since the copy would then read its own output
+/// until the disk is full.
+fn check_theme_location(app: &App) -> Result<()> {
+    let theme_dir = Path::new(&app.theme_directory).join(&app.theme_name);
+    for comp in app.checked_components() {
+        for path_str in comp.active_sources() {
+            check_source_location(&theme_dir, &expand_path(path_str), &comp.name)?;
+        }
+    }
+    Ok(())
+}
+
+/// `check_theme_location` for one `source` of `component`.
+fn check_source_location(theme_dir: &Path, source: &Path, component: &str) -> Result<()> {
+    let theme_dir = resolved_path(theme_dir);
+    let source = resolved_path(source);
+    if theme_dir.starts_with(&source) {
+        anyhow::bail!(
+            "The theme folder {} is inside {} ({}), choose another directory",
+            theme_dir.display(),
+            source.display(),
+            component
+        );
+    }
+    if source.starts_with(&theme_dir) {
+        anyhow::bail!(
+            "{} ({}) is inside the theme folder {}, choose another directory",
+            source.display(),
+            component,
+            theme_dir.display()
+        );
+    }
+    Ok(())
+}
+
+/// `path` made absolute with links in its parents resolved, so different
+/// spellings of a folder compare equal. The last component is kept as it is:
+/// a source that `--link` turned into a link into the theme is not inside it.
+fn resolved_path(path: &Path) -> std::path::PathBuf {
+    let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let (Some(parent), Some(name)) = (absolute.parent(), absolute.file_name()) else {
+        return absolute;
+    };
+    match parent.canonicalize() {
+        Ok(parent) => parent.join(name),
+        Err(_) => resolved_path(parent).join(name),
+    }
+}
+
+/// Moves `source` to `dest` in the theme and leaves a symlink to it in its
+/// place, like `stow`. When the theme is on another filesystem the files are
+/// copied and the original is only removed once everything was copied.
+fn move_and_link(source: &Path, dest: &Path) -> Result<()> {
+    // A trailing slash would make the link point at the moved folder's parent
+    let source: &Path = &source.components().collect::<std::path::PathBuf>();
+    let dest = dest.to_path_buf();
+    if dest.starts_with(source) {
+        anyhow::bail!("The theme directory is inside {}", source.display());
+    }
+    if fs::symlink_metadata(&dest).is_ok() {
+        anyhow::bail!("{} already exists", dest.display());
+    }
+
+    let renamed = fs::rename(source, &dest).is_ok();
+    if !renamed {
+        let copied =
+            copy_as(source, &dest, &GlobSet::empty(), &mut |_, _| Ok(())).and_then(|skipped| {
+                match skipped.into_iter().next() {
+                    Some((path, e)) => Err(e.context(format!("Failed to copy {}", path.display()))),
+                    None => Ok(()),
+                }
+            });
+        if let Err(e) = copied {
+            let _ = fs::remove_dir_all(&dest).or_else(|_| fs::remove_file(&dest));
+            return Err(e.context("Nothing was moved"));
+        }
+        if fs::symlink_metadata(source)?.is_dir() {
+            fs::remove_dir_all(source)
+        } else {
+            fs::remove_file(source)
+        }
+        .with_context(|| format!("Failed to remove {} after copying it", source.display()))?;
+    }
+
+    std::os::unix::fs::symlink(&dest, source).or_else(|e| {
+        if renamed {
+            fs::rename(&dest, source)?;
+        }
+        Err(anyhow::Error::from(e).context(format!(
+            "Failed to link {} to {}",
+            source.display(),
+            dest.display()
+        )))
+    })
+}
+
+/// An entry inside a copied folder that couldn't be copied, and why.
+type SkippedEntry = (std::path::PathBuf, anyhow::Error);
+
+/// Copies `source` to `dest_path`, creating its parent folders. Entries
+/// inside it whose name matches `exclude` are skipped. `on_file` is called with the
+/// source and destination after each regular file or symlink so callers can
+/// report progress; returning `Cancelled` from it stops the whole copy.
+///
+/// Entries that fail to copy are returned instead of aborting the rest, so
+/// one unreadable file or broken link doesn't lose a whole theme folder; an
+/// error means `source` itself couldn't be copied.
+///
+/// `source` itself is followed if it is a symlink (dotfile managers link whole
+/// config folders), but links inside it are recreated as links and mode bits
+/// are kept, so cursor and icon themes keep their structure.
+fn copy_as(
+    source: &std::path::Path,
+    dest_path: &std::path::Path,
+    exclude: &GlobSet,
+    on_file: &mut dyn FnMut(&Path, &Path) -> Result<()>,
+) -> Result<Vec<SkippedEntry>> {
+    let metadata =
+        fs::metadata(source).with_context(|| format!("Failed to read {}", source.display()))?;
+    if let Some(parent) = dest_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut skipped = Vec::new();
+    copy_entry(source, &metadata, dest_path, exclude, on_file, &mut skipped)?;
+    Ok(skipped)
+}
+
+fn copy_entry(
+    source: &std::path::Path,
+    metadata: &fs::Metadata,
+    dest_path: &std::path::Path,
+    exclude: &GlobSet,
+    on_file: &mut dyn FnMut(&Path, &Path) -> Result<()>,
+    skipped: &mut Vec<SkippedEntry>,
+) -> Result<()> {
+    let file_type = metadata.file_type();
+
+    // Never write through an existing link or over a different kind of entry
+    if let Ok(existing) = fs::symlink_metadata(dest_path) {
+        let existing_type = existing.file_type();
+        if existing_type.is_symlink() || existing_type.is_dir() != file_type.is_dir() {
+            if existing_type.is_dir() {
+                fs::remove_dir_all(dest_path)?;
+            } else {
+                fs::remove_file(dest_path)?;
+            }
+        }
+    }
+
+    if file_type.is_symlink() {
+        let target = fs::read_link(source)?;
+        std::os::unix::fs::symlink(&target, dest_path)
+            .with_context(|| format!("Failed to link {}", dest_path.display()))?;
+        on_file(source, dest_path)?;
+    } else if file_type.is_file() {
+        // fs::copy also copies the permission bits
+        fs::copy(source, dest_path)
+            .with_context(|| format!("Failed to copy {}", source.display()))?;
+        on_file(source, dest_path)?;
+    } else if file_type.is_dir() {
+        fs::create_dir_all(dest_path)?;
+        for entry in
+            fs::read_dir(source).with_context(|| format!("Failed to read {}", source.display()))?
+        {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    skipped.push((source.to_path_buf(), e.into()));
+                    continue;
+                }
+            };
+            if exclude.is_match(entry.file_name()) {
+                continue;
+            }
+            let result = entry
+                .metadata()
+                .map_err(anyhow::Error::from)
+                .and_then(|entry_metadata| {
+                    copy_entry(
+                        &entry.path(),
+                        &entry_metadata,
+                        &dest_path.join(entry.file_name()),
+                        exclude,
+                        on_file,
+                        skipped,
+                    )
+                });
+            if let Err(e) = result {
+                if e.is::<Cancelled>() {
+                    return Err(e);
+                }
+                log::warn!("Skipped {}: {:#}", entry.path().display(), e);
+                skipped.push((entry.path(), e));
+            }
+        }
+        // Keep the directory writable for us so later merges and restores work
+        let mode = metadata.permissions().mode() | 0o700;
+        fs::set_permissions(dest_path, fs::Permissions::from_mode(mode))?;
+    }
+    Ok(())
+}
+
+/// Number of files and links `copy_as` would copy from `path`.
+fn count_files(path: &std::path::Path, exclude: &GlobSet) -> u64 {
+    scan_files(path, exclude).0
+}
+
+/// Number of files and links under `path` and their combined size in bytes,
+/// following `path` itself but not the links inside it.
+fn scan_files(path: &std::path::Path, exclude: &GlobSet) -> (u64, u64) {
+    match fs::metadata(path) {
+        Ok(metadata) => scan_entry(path, &metadata, exclude),
+        Err(_) => (0, 0),
+    }
+}
+
+fn scan_entry(path: &std::path::Path, metadata: &fs::Metadata, exclude: &GlobSet) -> (u64, u64) {
+    let file_type = metadata.file_type();
+    if file_type.is_symlink() {
+        (1, 0)
+    } else if file_type.is_file() {
+        (1, metadata.len())
+    } else if file_type.is_dir() {
+        fs::read_dir(path)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .filter(|entry| !exclude.is_match(entry.file_name()))
+                    .filter_map(|entry| Some((entry.path(), entry.metadata().ok()?)))
+                    .map(|(path, metadata)| scan_entry(&path, &metadata, exclude))
+                    .fold((0, 0), |(files, bytes), (f, b)| (files + f, bytes + b))
+            })
+            .unwrap_or((0, 0))
+    } else {
+        (0, 0)
+    }
+}
+
+/// Whether anything below the folder `path` matches `exclude`. Links inside
+/// it aren't followed.
+fn contains_excluded(path: &Path, exclude: &GlobSet) -> bool {
+    fs::read_dir(path).is_ok_and(|entries| {
+        entries.flatten().any(|entry| {
+            exclude.is_match(entry.file_name())
+                || (entry.file_type().is_ok_and(|file_type| file_type.is_dir())
+                    && contains_excluded(&entry.path(), exclude))
+        })
+    })
+}
+
+/// Formats a byte count with binary units, e.g. `1.5 MiB`.
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// A detected setting and where it was read from; a config file and the
+/// live setting (gsettings, kreadconfig) can disagree until it is applied.
+#[derive(Debug, Clone)]
+pub struct Detection {
+    pub value: String,
+    /// `file:<path>`, `cmd:<program>`, `env:<variable>` or `dir:<path>`, with
+    /// paths relative to the config folder when they are inside it.
+    pub source: String,
+}
+
+impl Detection {
+    fn file(value: impl Into<String>, path: &Path) -> Self {
+        Self {
+            value: value.into(),
+            source: format!("file:{}", short_path(path)),
+        }
+    }
+
+    fn command(value: impl Into<String>, program: &str) -> Self {
+        Self {
+            value: value.into(),
+            source: format!("cmd:{}", program),
+        }
+    }
+
+    fn env(value: impl Into<String>, variable: &str) -> Self {
+        Self {
+            value: value.into(),
+            source: format!("env:{}", variable),
+        }
+    }
+
+    fn dir(value: impl Into<String>, path: &Path) -> Self {
+        Self {
+            value: value.into(),
+            source: format!("dir:{}", short_path(path)),
+        }
+    }
+
+    /// Combines several findings into one, listing each source once.
+    fn join(parts: Vec<Self>) -> Option<Self> {
+        let mut sources: Vec<&str> = Vec::new();
+        for part in &parts {
+            if !sources.contains(&part.source.as_str()) {
+                sources.push(&part.source);
+            }
+        }
+        let source = sources.join(", ");
+        let value = parts
+            .iter()
+            .map(|part| part.value.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        (!parts.is_empty()).then_some(Self { value, source })
+    }
+}
 
-                                    if let Some(entry) = selected_entry {
-                                        if entry.ends_with('/') {
-                                            // Navigate into subdirectory
-                                            let new_path =
-                                                std::path::Path::new(&app.theme_directory)
-                                                    .join(entry.trim_end_matches('/'));
-                                            app.theme_directory =
-                                                new_path.to_string_lossy().to_string();
-                                            app.directory_selected = 0;
-                                            update_directory_entries(app);
-                                        }
-                                    } else {
-                                        // Accept current directory
-                                        app.mode = Mode::Summary;
-                                    }
-                                }
-                                KeyCode::Up => {
-                                    if !app.directory_entries.is_empty() {
-                                        app.directory_selected = if app.directory_selected == 0 {
-                                            app.directory_entries.len() - 1
-                                        } else {
-                                            app.directory_selected - 1
-                                        };
-                                    }
-                                }
-                                KeyCode::Down => {
-                                    if !app.directory_entries.is_empty() {
-                                        app.directory_selected = (app.directory_selected + 1)
-                                            % app.directory_entries.len();
-                                    }
-                                }
-                                KeyCode::Tab => {
-                                    // Create new directory functionality would go here
-                                    // For now, just accept current directory
-                                    app.mode = Mode::Summary;
-                                }
-                                _ => {}
-                            }
-                        }
-                        Mode::Summary => match key.code {
-                            KeyCode::Esc => app.mode = Mode::Selecting,
-                            KeyCode::Enter => {
-                                app.permission_issues = check_permissions(&app);
-                                if app.permission_issues.is_empty() {
-                                    create_theme(&app)?;
-                                    break;
-                                } else {
-                                    app.mode = Mode::PermissionCheck;
-                                }
-                            }
-                            _ => {}
-                        },
-                        Mode::PermissionCheck => {
-                            match key.code {
-                                KeyCode::Esc => app.mode = Mode::Summary,
-                                KeyCode::Char('1') => {
-                                    // Re-run with sudo
-                                    let current_exe =
-                                        env::current_exe().context("Failed to get current exe")?;
-                                    let args: Vec<String> = env::args().collect();
-                                    let status = Command::new("sudo")
-                                        .arg(current_exe)
-                                        .args(&args[1..])
-                                        .status()?;
+/// `path` relative to the config folder, or with the home folder as `~`.
+fn short_path(path: &Path) -> String {
+    if let Some(relative) =
+        config_dir().and_then(|dir| path.strip_prefix(dir).ok().map(Path::to_path_buf))
+    {
+        return relative.display().to_string();
+    }
+    match home_dir().and_then(|home| path.strip_prefix(home).ok().map(Path::to_path_buf)) {
+        Some(relative) => format!("~/{}", relative.display()),
+        None => path.display().to_string(),
+    }
+}
 
-                                    if status.success() {
-                                        break;
-                                    } else {
-                                        app.message = "Sudo execution failed".to_string();
-                                        app.mode = Mode::Selecting;
-                                    }
-                                }
-                                KeyCode::Char('2') => {
-                                    // Generate chmod commands
-                                    let chmod_commands =
-                                        generate_chmod_commands(&app.permission_issues);
+// Style detection functions
+fn detect_gtk_theme() -> Option<Detection> {
+    let mut found = Vec::new();
+
+    // Check GTK3 and GTK4 settings
+    for (label, dir) in [("GTK3", ".config/gtk-3.0"), ("GTK4", ".config/gtk-4.0")] {
+        let config_dir = home_dir()?.join(dir);
+        let settings = config_dir.join("settings.ini");
+        // Hand-written overrides, and all that libadwaita apps honour
+        let css = config_dir.join("gtk.css");
+        let custom_css = fs::read_to_string(&css).is_ok_and(|content| !content.trim().is_empty());
+        let theme = fs::read_to_string(&settings)
+            .ok()
+            .and_then(|content| read_ini_value(&content, "Settings", "gtk-theme-name"));
+        match theme {
+            Some(theme) if custom_css => found.push(Detection::file(
+                format!(
+                    "{}: {} (custom CSS present)",
+                    label,
+                    theme.trim_matches('"')
+                ),
+                &settings,
+            )),
+            Some(theme) => found.push(Detection::file(
+                format!("{}: {}", label, theme.trim_matches('"')),
+                &settings,
+            )),
+            None if custom_css => found.push(Detection::file(
+                format!("{}: custom CSS present", label),
+                &css,
+            )),
+            None => {}
+        }
+    }
+
+    if !found.is_empty() {
+        return Detection::join(found);
+    }
+
+    // Check dconf settings (requires dconf command)
+    if let Some(output) = run_command(
+        "gsettings",
+        &["get", "org.gnome.desktop.interface", "gtk-theme"],
+    ) {
+        if output.status.success() {
+            let theme = String::from_utf8_lossy(&output.stdout);
+            let theme = theme.trim().trim_matches('\'');
+            return Some(Detection::command(format!("GTK: {}", theme), "gsettings"));
+        }
+    }
+
+    None
+}
+
+/// The desktop-wide dark or light preference: "dark" or "light" and where it
+/// was found. GNOME keeps it in `color-scheme`, GTK in settings.ini, and KDE
+/// derives it from the window background of the color scheme.
+fn color_preference() -> Option<(&'static str, Detection)> {
+    if let Some(output) = run_command(
+        "gsettings",
+        &["get", "org.gnome.desktop.interface", "color-scheme"],
+    ) {
+        let scheme = String::from_utf8_lossy(&output.stdout);
+        let scheme = match scheme.trim().trim_matches('\'') {
+            "prefer-dark" => Some("dark"),
+            "prefer-light" => Some("light"),
+            _ => None,
+        };
+        if let Some(scheme) = scheme.filter(|_| output.status.success()) {
+            return Some((scheme, Detection::command("", "gsettings")));
+        }
+    }
+
+    let settings = config_dir()?.join("gtk-3.0/settings.ini");
+    if let Ok(content) = fs::read_to_string(&settings) {
+        if let Some(dark) =
+            read_ini_value(&content, "Settings", "gtk-application-prefer-dark-theme")
+        {
+            let scheme = if matches!(dark.as_str(), "1" | "true") {
+                "dark"
+            } else {
+                "light"
+            };
+            return Some((scheme, Detection::file("", &settings)));
+        }
+    }
+
+    let kdeglobals = config_dir()?.join("kdeglobals");
+    let content = fs::read_to_string(&kdeglobals).ok()?;
+    let background = read_ini_value(&content, "Colors:Window", "BackgroundNormal")?;
+    let rgb: Vec<f64> = background
+        .split(',')
+        .filter_map(|part| part.trim().parse().ok())
+        .collect();
+    let [r, g, b] = rgb[..] else {
+        return None;
+    };
+    let scheme = if 0.299 * r + 0.587 * g + 0.114 * b < 128.0 {
+        "dark"
+    } else {
+        "light"
+    };
+    Some((scheme, Detection::file("", &kdeglobals)))
+}
+
+/// `detection` with the dark/light preference added, e.g. "Prefer: dark".
+fn with_color_preference(detection: Option<Detection>) -> Option<Detection> {
+    let preference = color_preference().map(|(scheme, found)| Detection {
+        value: format!("Prefer: {}", scheme),
+        ..found
+    });
+    Detection::join(detection.into_iter().chain(preference).collect())
+}
+
+fn detect_icon_theme() -> Option<Detection> {
+    // KDE's own setting, pure Plasma setups may have no GTK config at all
+    let kdeglobals = config_dir()?.join("kdeglobals");
+    if let Ok(content) = fs::read_to_string(&kdeglobals) {
+        if let Some(theme) = read_ini_value(&content, "Icons", "Theme") {
+            return Some(Detection::file(format!("KDE: {}", theme), &kdeglobals));
+        }
+    }
+
+    // Check GTK3 settings for icons
+    let settings = config_dir()?.join("gtk-3.0/settings.ini");
+    if let Ok(content) = fs::read_to_string(&settings) {
+        for line in content.lines() {
+            if line.trim().starts_with("gtk-icon-theme-name=") {
+                let theme = line.split('=').nth(1)?.trim().trim_matches('"');
+                return Some(Detection::file(format!("Icons: {}", theme), &settings));
+            }
+        }
+    }
+
+    // Check gsettings
+    if let Some(output) = run_command(
+        "gsettings",
+        &["get", "org.gnome.desktop.interface", "icon-theme"],
+    ) {
+        if output.status.success() {
+            let theme = String::from_utf8_lossy(&output.stdout);
+            let theme = theme.trim().trim_matches('\'');
+            return Some(Detection::command(format!("Icons: {}", theme), "gsettings"));
+        }
+    }
+
+    None
+}
+
+fn detect_cursor_theme() -> Option<Detection> {
+    // Check GTK3 settings for cursor theme
+    let settings = config_dir()?.join("gtk-3.0/settings.ini");
+    if let Ok(content) = fs::read_to_string(&settings) {
+        for line in content.lines() {
+            if line.trim().starts_with("gtk-cursor-theme-name=") {
+                let theme = line.split('=').nth(1)?.trim().trim_matches('"');
+                return Some(Detection::file(format!("Cursor: {}", theme), &settings));
+            }
+        }
+    }
+
+    // The default cursor theme X and most toolkits fall back to
+    for index in [
+        home_dir()?.join(".icons/default/index.theme"),
+        data_dir()?.join("icons/default/index.theme"),
+    ] {
+        if let Ok(content) = fs::read_to_string(&index) {
+            if let Some(theme) = read_ini_value(&content, "Icon Theme", "Inherits") {
+                let theme = theme.split(',').next().unwrap_or_default().trim();
+                return Some(Detection::file(format!("Cursor: {}", theme), &index));
+            }
+        }
+    }
+
+    // X resources, read by Xcursor itself; Wayland clients don't use them
+    let xresources = if session_type() == SessionType::Wayland {
+        Vec::new()
+    } else {
+        vec![
+            home_dir()?.join(".Xresources"),
+            home_dir()?.join(".Xdefaults"),
+        ]
+    };
+    for file in xresources {
+        let Ok(content) = fs::read_to_string(&file) else {
+            continue;
+        };
+        if let Some(theme) = xresource_value(&content, "Xcursor.theme") {
+            let value = match xresource_value(&content, "Xcursor.size") {
+                Some(size) => format!("Cursor: {} ({})", theme, size),
+                None => format!("Cursor: {}", theme),
+            };
+            return Some(Detection::file(value, &file));
+        }
+    }
+
+    // Check gsettings
+    if let Some(output) = run_command(
+        "gsettings",
+        &["get", "org.gnome.desktop.interface", "cursor-theme"],
+    ) {
+        if output.status.success() {
+            let theme = String::from_utf8_lossy(&output.stdout);
+            let theme = theme.trim().trim_matches('\'');
+            return Some(Detection::command(
+                format!("Cursor: {}", theme),
+                "gsettings",
+            ));
+        }
+    }
+
+    None
+}
+
+/// The value of `key` (e.g. `Xcursor.theme`) in an X resources file. Loose
+/// bindings like `Xcursor*theme` match as well.
+fn xresource_value(content: &str, key: &str) -> Option<String> {
+    content.lines().find_map(|line| {
+        let line = line.trim();
+        if line.starts_with('!') || line.starts_with('#') {
+            return None;
+        }
+        let (name, value) = line.split_once(':')?;
+        (name.trim().replace('*', ".") == key).then(|| value.trim().to_string())
+    })
+}
+
+/// The Qt platform theme and the qt5ct/qt6ct style and icon theme, which
+/// decide how Qt apps look outside Plasma.
+fn detect_qt_style() -> Option<Detection> {
+    let mut parts = Vec::new();
+
+    // The variable belongs to this session, not to a --home or --user export
+    if HOME_OVERRIDE.get().is_none() {
+        if let Some(platform) = env::var("QT_QPA_PLATFORMTHEME")
+            .ok()
+            .filter(|platform| !platform.is_empty())
+        {
+            parts.push(Detection::env(
+                format!("Platform: {}", platform),
+                "QT_QPA_PLATFORMTHEME",
+            ));
+        }
+    }
+
+    for (label, file) in [("Qt5", "qt5ct/qt5ct.conf"), ("Qt6", "qt6ct/qt6ct.conf")] {
+        let path = config_dir()?.join(file);
+        let Ok(content) = fs::read_to_string(&path) else {
+            continue;
+        };
+        let value =
+            |key| read_ini_value(&content, "Appearance", key).filter(|value| !value.is_empty());
+        let value = match (value("style"), value("icon_theme")) {
+            (Some(style), Some(icons)) => format!("{}: {} (icons: {})", label, style, icons),
+            (Some(style), None) => format!("{}: {}", label, style),
+            (None, Some(icons)) => format!("{} icons: {}", label, icons),
+            (None, None) => continue,
+        };
+        parts.push(Detection::file(value, &path));
+    }
+
+    Detection::join(parts)
+}
+
+fn detect_color_scheme() -> Option<Detection> {
+    // Check KDE color schemes, naming the .colors file behind the setting
+    let kdeglobals = config_dir()?.join("kdeglobals");
+    if let Some(scheme) = kde_color_scheme_name() {
+        return Some(match color_scheme_file(&scheme) {
+            Some(file) => {
+                let location = if file.starts_with(get_user_home_dir()) {
+                    "user"
+                } else {
+                    "system"
+                };
+                Detection::file(format!("KDE: {} ({})", scheme, location), &file)
+            }
+            None => Detection::file(format!("KDE: {} (file not found)", scheme), &kdeglobals),
+        });
+    }
+
+    // Check Plasma colors
+    if let Some(color) = kreadconfig(&["--group", "Colors:Window", "--key", "BackgroundNormal"]) {
+        return Some(Detection::command(
+            format!("Plasma: {}", color),
+            "kreadconfig",
+        ));
+    }
+
+    None
+}
+
+/// The `ColorScheme=` of kdeglobals. Plasma writes it to [General], but
+/// older and hand-edited files have it elsewhere, so any group is accepted.
+fn kde_color_scheme_name() -> Option<String> {
+    let content = fs::read_to_string(config_dir()?.join("kdeglobals")).ok()?;
+    read_ini_value(&content, "General", "ColorScheme")
+        .or_else(|| {
+            content.lines().find_map(|line| {
+                let (key, value) = line.split_once('=')?;
+                (key.trim() == "ColorScheme").then(|| value.trim().to_string())
+            })
+        })
+        .filter(|scheme| !scheme.is_empty())
+}
+
+/// The `.colors` file of the color scheme `name`, user schemes first. Files
+/// are usually named after the scheme, otherwise their `Name=` is matched.
+fn color_scheme_file(name: &str) -> Option<std::path::PathBuf> {
+    let data_dirs = env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_string());
+    let scheme_dirs: Vec<std::path::PathBuf> = std::iter::once(expand_tilde("~/.local/share"))
+        .chain(data_dirs.split(':').map(std::path::PathBuf::from))
+        .map(|dir| dir.join("color-schemes"))
+        .collect();
+
+    if let Some(file) = scheme_dirs
+        .iter()
+        .map(|dir| dir.join(format!("{}.colors", name)))
+        .find(|file| file.is_file())
+    {
+        return Some(file);
+    }
+    scheme_dirs.iter().find_map(|dir| {
+        fs::read_dir(dir)
+            .ok()?
+            .flatten()
+            .map(|entry| entry.path())
+            .find(|file| {
+                file.extension().is_some_and(|ext| ext == "colors")
+                    && fs::read_to_string(file).is_ok_and(|content| {
+                        read_ini_value(&content, "General", "Name").as_deref() == Some(name)
+                    })
+            })
+    })
+}
+
+fn detect_kwin_scripts() -> Option<Detection> {
+    let mut parts = Vec::new();
+
+    let scripts_dir = data_dir()?.join("kwin/scripts");
+    if let Ok(entries) = fs::read_dir(&scripts_dir) {
+        let mut scripts: Vec<String> = entries
+            .flatten()
+            .filter(|entry| entry.path().is_dir())
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .collect();
+        scripts.sort();
+        let value = match scripts.len() {
+            0 => None,
+            1..=3 => Some(format!("Scripts: {}", scripts.join(", "))),
+            n => Some(format!("Scripts: {} installed", n)),
+        };
+        if let Some(value) = value {
+            parts.push(Detection::dir(value, &scripts_dir));
+        }
+    }
+
+    // Rules are numbered groups; [General] count says how many there are
+    let rulesrc = config_dir()?.join("kwinrulesrc");
+    if let Ok(content) = fs::read_to_string(&rulesrc) {
+        let count = read_ini_value(&content, "General", "count")
+            .and_then(|count| count.parse::<u32>().ok())
+            .unwrap_or(0);
+        if count > 0 {
+            parts.push(Detection::file(
+                format!("Window rules: {}", count),
+                &rulesrc,
+            ));
+        }
+    }
+
+    Detection::join(parts)
+}
+
+/// The effects switched on in kwinrc `[Plugins]`, where KWin keeps
+/// `<effect>Enabled=true|false` for every effect changed from its default.
+/// Scripts are listed there too, so installed ones are left out.
+/// The plugins kwinrc switches on or off in `[Plugins]` (`<name>Enabled=`),
+/// effects and KWin scripts alike. Other values are left out.
+fn effect_plugins(kwinrc: &str) -> Vec<(String, bool)> {
+    let mut plugins = Vec::new();
+    let mut in_plugins = false;
+    for line in kwinrc.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_plugins = line == "[Plugins]";
+            continue;
+        }
+        if !in_plugins {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let Some(plugin) = key.trim().strip_suffix("Enabled") else {
+            continue;
+        };
+        match value.trim() {
+            "true" => plugins.push((plugin.to_string(), true)),
+            "false" => plugins.push((plugin.to_string(), false)),
+            _ => {}
+        }
+    }
+    plugins
+}
+
+fn detect_desktop_effects() -> Option<Detection> {
+    let kwinrc = config_dir()?.join("kwinrc");
+    let content = fs::read_to_string(&kwinrc).ok()?;
+    let scripts_dir = data_dir()?.join("kwin/scripts");
+
+    let (mut enabled, mut disabled) = (Vec::new(), 0);
+    for (effect, on) in effect_plugins(&content) {
+        if scripts_dir.join(&effect).is_dir() {
+            continue;
+        }
+        if on {
+            enabled.push(effect);
+        } else {
+            disabled += 1;
+        }
+    }
+    if enabled.is_empty() && disabled == 0 {
+        return None;
+    }
+
+    enabled.sort();
+    let mut value = match enabled.len() {
+        0 => "Effects: defaults".to_string(),
+        1..=3 => format!("Effects: {}", enabled.join(", ")),
+        n => format!("Effects: {} enabled", n),
+    };
+    if disabled > 0 {
+        value.push_str(&format!(" ({} turned off)", disabled));
+    }
+    Some(Detection::file(value, &kwinrc))
+}
+
+fn detect_window_decorations() -> Option<Detection> {
+    // Check KDE KWin window decorations
+    if let Some(decoration) = kreadconfig(&[
+        "--file",
+        "kwinrc",
+        "--group",
+        "org.kde.kdecoration2",
+        "--key",
+        "library",
+    ]) {
+        if decoration != "org.kde.kwin.aurorae" {
+            return Some(Detection::command(
+                format!("KWin: {}", decoration),
+                "kreadconfig",
+            ));
+        }
+    }
+
+    // Check KWin config directly
+    let kwinrc = config_dir()?.join("kwinrc");
+    if let Ok(content) = fs::read_to_string(&kwinrc) {
+        for line in content.lines() {
+            if line.trim().starts_with("plugin=") {
+                let plugin = line.split('=').nth(1)?.trim();
+                return Some(Detection::file(format!("KWin Plugin: {}", plugin), &kwinrc));
+            }
+        }
+    }
+
+    // Check for AwesomeWM decorations
+    let awesome = config_dir()?.join("awesome/rc.lua");
+    if let Ok(content) = fs::read_to_string(&awesome) {
+        for line in content.lines() {
+            if line.trim().contains("beautiful.init") {
+                return Some(Detection::file("AwesomeWM: Beautiful", &awesome));
+            }
+        }
+    }
+
+    // Check for Openbox theme
+    let openbox = config_dir()?.join("openbox/rc.xml");
+    if let Ok(content) = fs::read_to_string(&openbox) {
+        for line in content.lines() {
+            if line.trim().contains("<theme>") {
+                if let Some(start) = line.find("<name>") {
+                    if let Some(end) = line.find("</name>") {
+                        let theme = &line[start + 6..end];
+                        let value = format!("Openbox: {}", theme.trim());
+                        return Some(Detection::file(value, &openbox));
+                    }
+                }
+            }
+        }
+    }
+
+    None
+}
+
+fn detect_splash_screen() -> Option<Detection> {
+    // Plasma's own splash, shown between login and the desktop
+    let ksplashrc = config_dir()?.join("ksplashrc");
+    if let Ok(content) = fs::read_to_string(&ksplashrc) {
+        if read_ini_value(&content, "KSplash", "Engine").as_deref() == Some("none") {
+            return Some(Detection::file("KSplash: None", &ksplashrc));
+        }
+        if let Some(theme) = read_ini_value(&content, "KSplash", "Theme") {
+            return Some(Detection::file(format!("KSplash: {}", theme), &ksplashrc));
+        }
+    }
+
+    // Plymouth (boot splash): its config and the default theme link can be
+    // read by anyone, unlike the setter, which may need root
+    let plymouthd = Path::new("/etc/plymouth/plymouthd.conf");
+    if let Ok(content) = fs::read_to_string(plymouthd) {
+        let theme = read_ini_value(&content, "Daemon", "Theme").filter(|t| !t.is_empty());
+        if let Some(theme) = theme {
+            return Some(Detection::file(format!("Plymouth: {}", theme), plymouthd));
+        }
+    }
+    // Only the link says which theme is picked, a plain file is a theme of its own
+    let default_theme = Path::new("/usr/share/plymouth/themes/default.plymouth");
+    let linked = fs::symlink_metadata(default_theme).is_ok_and(|metadata| metadata.is_symlink());
+    if let Some(theme) = fs::canonicalize(default_theme)
+        .ok()
+        .filter(|_| linked)
+        .and_then(|target| Some(target.file_stem()?.to_string_lossy().into_owned()))
+    {
+        return Some(Detection::file(
+            format!("Plymouth: {}", theme),
+            default_theme,
+        ));
+    }
+    if let Some(output) = run_command("plymouth-set-default-theme", &["--show-current"]) {
+        if output.status.success() {
+            let theme_str = String::from_utf8_lossy(&output.stdout);
+            let theme = theme_str.trim();
+            if !theme.is_empty() {
+                return Some(Detection::command(
+                    format!("Plymouth: {}", theme),
+                    "plymouth-set-default-theme",
+                ));
+            }
+        }
+    }
+
+    // Check GRUB themes
+    let grub = Path::new("/etc/default/grub");
+    if let Ok(content) = fs::read_to_string(grub) {
+        for line in content.lines() {
+            if line.trim().starts_with("GRUB_THEME=") {
+                let theme = line.split('=').nth(1)?.trim().trim_matches('"');
+                return Some(Detection::file(format!("GRUB: {}", theme), grub));
+            }
+        }
+    }
 
-                                    // Try to copy to clipboard
-                                    if let Ok(()) = copy_to_clipboard(&chmod_commands) {
-                                        app.message =
-                                            "Chmod commands copied to clipboard!".to_string();
-                                    } else {
-                                        println!(
-                                            "\n=== Chmod Commands ===\n{}\n=== End Commands ===\n",
-                                            chmod_commands
-                                        );
-                                        app.message =
-                                            "Chmod commands printed to terminal".to_string();
-                                    }
-                                    app.mode = Mode::Selecting;
-                                }
-                                _ => {}
-                            }
-                        }
+    // Check for available splash themes
+    let plymouth_themes = Path::new("/usr/share/plymouth/themes");
+    if plymouth_themes.exists() {
+        if let Ok(entries) = fs::read_dir(plymouth_themes) {
+            for entry in entries.flatten() {
+                if let Ok(file_type) = entry.file_type() {
+                    if file_type.is_dir() {
+                        return Some(Detection::dir("Plymouth: Available", plymouth_themes));
                     }
                 }
             }
         }
     }
 
-    Ok(())
+    None
 }
 
-fn update_directory_entries(app: &mut App) {
-    app.directory_entries.clear();
-    app.directory_selected = 0;
+/// The SDDM theme in effect and the file that sets it. SDDM reads the `*.conf`
+/// files of the drop-in folders in alphabetical order, system defaults first,
+/// then `/etc/sddm.conf`, and the last value read wins. So a value left in
+/// `/etc/sddm.conf` does override the drop-ins, as it does for SDDM itself.
+fn sddm_current_theme() -> Option<(std::path::PathBuf, String)> {
+    let mut files = Vec::new();
+    for dir in ["/usr/lib/sddm/sddm.conf.d", "/etc/sddm.conf.d"] {
+        // Leftovers like `*.conf.bak` or `*.rpmnew` aren't read by SDDM
+        let mut drop_ins: Vec<_> = fs::read_dir(dir)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .map(|entry| entry.path())
+                    .filter(|path| path.extension().is_some_and(|ext| ext == "conf"))
+                    .collect()
+            })
+            .unwrap_or_default();
+        drop_ins.sort();
+        files.extend(drop_ins);
+    }
+    files.push(std::path::PathBuf::from("/etc/sddm.conf"));
 
-    let path = std::path::Path::new(&app.theme_directory);
-    if let Ok(entries) = fs::read_dir(path) {
-        for entry in entries.flatten() {
-            if let Ok(file_type) = entry.file_type() {
-                let name = entry.file_name().to_string_lossy().to_string();
-                if file_type.is_dir() && !name.starts_with('.') {
-                    app.directory_entries.push(name + "/");
-                }
+    files.into_iter().rev().find_map(|file| {
+        let theme = read_ini_value(&fs::read_to_string(&file).ok()?, "Theme", "Current")?;
+        Some((file, theme))
+    })
+}
+
+fn detect_sddm_theme() -> Option<Detection> {
+    let (file, theme) = sddm_current_theme()?;
+    if theme.is_empty() {
+        return Some(Detection::file("SDDM: Default", &file));
+    }
+    Some(Detection::file(format!("SDDM: {}", theme), &file))
+}
+
+/// The background image of the active SDDM theme, which may live outside the
+/// theme folder (often under `/usr/share/wallpapers/`). `theme.conf.user`
+/// overrides `theme.conf`, and relative paths are relative to the theme.
+fn sddm_background_path() -> Option<std::path::PathBuf> {
+    let (_, theme) = sddm_current_theme()?;
+    if theme.is_empty() {
+        return None;
+    }
+    let theme_dir = Path::new("/usr/share/sddm/themes").join(&theme);
+    let background = ["theme.conf.user", "theme.conf"].iter().find_map(|name| {
+        let content = fs::read_to_string(theme_dir.join(name)).ok()?;
+        read_ini_value(&content, "General", "background")
+            .or_else(|| read_ini_value(&content, "General", "Background"))
+            .filter(|value| !value.is_empty())
+    })?;
+    let background = background.trim_matches('"');
+    let path = if Path::new(background).is_absolute() {
+        std::path::PathBuf::from(background)
+    } else {
+        theme_dir.join(background)
+    };
+    path.is_file().then_some(path)
+}
+
+fn detect_terminal_theme() -> Option<Detection> {
+    // Check alacritty, alacritty.toml replaced the YAML config in 0.13
+    let alacritty = config_dir()?.join("alacritty/alacritty.toml");
+    if let Ok(content) = fs::read_to_string(&alacritty) {
+        if let Ok(config) = content.parse::<toml::Table>() {
+            if let Some(import) = alacritty_imports(&config).last() {
+                let name = Path::new(import)
+                    .file_stem()
+                    .map_or(import.clone(), |stem| stem.to_string_lossy().to_string());
+                return Some(Detection::file(format!("Alacritty: {}", name), &alacritty));
             }
+            if config.contains_key("colors") {
+                return Some(Detection::file("Alacritty: Custom colors", &alacritty));
+            }
+        }
+    }
+    let alacritty_yml = config_dir()?.join("alacritty/alacritty.yml");
+    if let Ok(content) = fs::read_to_string(&alacritty_yml) {
+        for line in content.lines() {
+            if line.trim().starts_with("colors:") || line.trim().contains("primary:") {
+                return Some(Detection::file("Alacritty: Custom theme", &alacritty_yml));
+            }
+        }
+    }
+
+    // Check kitty
+    let kitty = config_dir()?.join("kitty/kitty.conf");
+    if let Ok(content) = fs::read_to_string(&kitty) {
+        for line in content.lines() {
+            if line.trim().starts_with("include") && line.contains("theme") {
+                let theme = line.split_whitespace().nth(1)?;
+                return Some(Detection::file(format!("Kitty: {}", theme), &kitty));
+            }
+        }
+    }
+
+    // Check gnome-terminal
+    if let Some(output) = run_command(
+        "gsettings",
+        &[
+            "get",
+            "org.gnome.Terminal.Profiles:/org/gnome/terminal/legacy/profiles:/",
+            "default-profile",
+        ],
+    ) {
+        if output.status.success() {
+            return Some(Detection::command(
+                "GNOME Terminal: Configured",
+                "gsettings",
+            ));
         }
-        app.directory_entries.sort();
     }
+
+    None
 }
 
-fn create_theme(app: &App) -> Result<()> {
-    let theme_dir = std::path::Path::new(&app.theme_directory).join(&app.theme_name);
+/// Files an Alacritty config imports; `general.import` since 0.14, a
+/// top-level `import` before that.
+fn alacritty_imports(config: &toml::Table) -> Vec<String> {
+    config
+        .get("general")
+        .and_then(|general| general.get("import"))
+        .or_else(|| config.get("import"))
+        .and_then(toml::Value::as_array)
+        .map(|imports| {
+            imports
+                .iter()
+                .filter_map(toml::Value::as_str)
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
 
-    // Ensure we have absolute path for display
-    let display_theme_dir = if theme_dir.is_absolute() {
-        theme_dir.clone()
-    } else {
-        std::env::current_dir()
-            .context("Failed to get current directory")?
-            .join(&theme_dir)
+/// Theme files imported by `alacritty.toml`, which often live outside
+/// `~/.config/alacritty/`.
+fn alacritty_theme_files() -> Vec<std::path::PathBuf> {
+    let Some(config) = config_dir()
+        .and_then(|dir| fs::read_to_string(dir.join("alacritty/alacritty.toml")).ok())
+        .and_then(|content| content.parse::<toml::Table>().ok())
+    else {
+        return Vec::new();
     };
+    alacritty_imports(&config)
+        .iter()
+        .map(|import| expand_tilde(import))
+        .filter(|path| path.is_file())
+        .collect()
+}
 
-    fs::create_dir_all(&display_theme_dir)?;
+fn detect_wm_theme() -> Option<Detection> {
+    // Wayland compositors export their IPC sockets
+    if std::env::var("HYPRLAND_INSTANCE_SIGNATURE").is_ok() {
+        return Some(Detection::env(
+            "WM: Hyprland",
+            "HYPRLAND_INSTANCE_SIGNATURE",
+        ));
+    }
 
-    let mut copied_files = Vec::new();
-    let mut skipped_files = Vec::new();
+    if std::env::var("SWAYSOCK").is_ok() {
+        return Some(Detection::env("WM: Sway", "SWAYSOCK"));
+    }
 
-    // Show user what we're doing
-    println!("\n🔍 Scanning for theme files...\n");
+    // Check current window manager
+    if let Ok(desktop) = std::env::var("XDG_CURRENT_DESKTOP") {
+        if !desktop.is_empty() {
+            return Some(Detection::env(
+                format!("WM: {}", desktop),
+                "XDG_CURRENT_DESKTOP",
+            ));
+        }
+    }
 
-    for comp in app.checked_components() {
-        let component_dir = display_theme_dir.join(comp.name.replace(&[' ', '/'][..], "_"));
-        fs::create_dir_all(&component_dir)?;
+    // Check for specific window managers
+    if std::env::var("I3SOCK").is_ok() {
+        return Some(Detection::env("WM: i3", "I3SOCK"));
+    }
+
+    if std::env::var("BSPWM_SOCKET").is_ok() {
+        return Some(Detection::env("WM: bspwm", "BSPWM_SOCKET"));
+    }
+
+    // Check processes
+    if let Some(output) = run_command(
+        "ps",
+        &["-u", std::env::var("USER").unwrap_or_default().as_str()],
+    ) {
+        let output_str = String::from_utf8_lossy(&output.stdout);
+        if output_str.contains("openbox") {
+            return Some(Detection::command("WM: Openbox", "ps"));
+        }
+        if output_str.contains("xfwm4") {
+            return Some(match xfconf_value("xfwm4", "/general/theme") {
+                Some(theme) => Detection {
+                    value: format!("WM: Xfwm4 ({})", theme.value),
+                    source: format!("cmd:ps, {}", theme.source),
+                },
+                None => Detection::command("WM: Xfwm4", "ps"),
+            });
+        }
+        if output_str.contains("kwin") {
+            return Some(Detection::command("WM: KWin", "ps"));
+        }
+    }
 
-        println!("📁 Processing: {}", comp.name);
+    None
+}
 
-        for path_str in &comp.source_paths {
-            let path = expand_tilde(path_str);
-            println!("   Checking: {} -> {}", path_str, path.display());
+/// The Xfce GTK and window manager themes.
+fn detect_xfce_theme() -> Option<Detection> {
+    let theme = xfconf_value("xsettings", "/Net/ThemeName").map(|theme| Detection {
+        value: format!("Xfce: {}", theme.value),
+        ..theme
+    });
+    let wm_theme = xfconf_value("xfwm4", "/general/theme").map(|theme| Detection {
+        value: format!("Xfwm4: {}", theme.value),
+        ..theme
+    });
+    Detection::join(theme.into_iter().chain(wm_theme).collect())
+}
 
-            if path.exists() {
-                if let Err(e) = copy_recursive(&path, &component_dir) {
-                    println!("   ❌ Failed to copy: {}", e);
-                    skipped_files.push(format!("{}: {} ({})", comp.name, path.display(), e));
-                } else {
-                    copied_files.push(format!("{}: {}", comp.name, path.display()));
-                    println!("   ✓ Successfully copied");
-                }
-            } else {
-                println!("   ⚠ Path not found");
-                skipped_files.push(format!("{}: {} (not found)", comp.name, path.display()));
+/// An xfconf setting, from `xfconf-query` or, when Xfce isn't running, from
+/// the channel's XML file.
+fn xfconf_value(channel: &str, property: &str) -> Option<Detection> {
+    if let Some(output) = run_command("xfconf-query", &["-c", channel, "-p", property]) {
+        if output.status.success() {
+            let value = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            return (!value.is_empty()).then(|| Detection::command(value, "xfconf-query"));
+        }
+    }
+
+    // Properties are nested per path segment, the last one holds the value
+    let file = config_dir()?
+        .join("xfce4/xfconf/xfce-perchannel-xml")
+        .join(format!("{}.xml", channel));
+    let content = fs::read_to_string(&file).ok()?;
+    let name = format!("name=\"{}\"", property.rsplit('/').next()?);
+    let line = content.lines().find(|line| line.contains(&name))?;
+    let value = line.split("value=\"").nth(1)?.split('"').next()?;
+    (!value.is_empty()).then(|| Detection::file(value, &file))
+}
+
+/// The Shell theme set through the User Themes extension, or the number of
+/// installed extensions when it isn't used.
+fn detect_gnome_shell_theme() -> Option<Detection> {
+    if let Some(output) = run_command(
+        "gsettings",
+        &["get", "org.gnome.shell.extensions.user-theme", "name"],
+    ) {
+        if output.status.success() {
+            let theme = String::from_utf8_lossy(&output.stdout);
+            let theme = theme.trim().trim_matches('\'');
+            let theme = if theme.is_empty() { "Default" } else { theme };
+            return Some(Detection::command(format!("Shell: {}", theme), "gsettings"));
+        }
+    }
+
+    let extensions_dir = data_dir()?.join("gnome-shell/extensions");
+    let extensions = fs::read_dir(&extensions_dir)
+        .ok()?
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+        .count();
+    (extensions > 0).then(|| {
+        Detection::dir(
+            format!("Extensions: {} installed", extensions),
+            &extensions_dir,
+        )
+    })
+}
+
+fn detect_shell_theme() -> Option<Detection> {
+    let shell = detect_shell();
+    Detection::join(shell.into_iter().chain(detect_prompt_theme()).collect())
+}
+
+fn detect_shell() -> Option<Detection> {
+    // Check current shell
+    if let Ok(shell) = std::env::var("SHELL") {
+        if shell.contains("zsh") {
+            let zshrc = home_dir()?.join(".zshrc");
+            if zshrc.is_file() {
+                // The Oh My Zsh theme is reported with the prompt
+                return Some(Detection::env("Shell: Zsh", "SHELL"));
             }
+        } else if shell.contains("bash") {
+            return Some(Detection::env("Shell: Bash", "SHELL"));
+        } else if shell.contains("fish") {
+            return Some(Detection::env("Shell: Fish", "SHELL"));
         }
-        println!();
     }
 
-    // Create theme metadata
-    let metadata_file = display_theme_dir.join("theme_info.txt");
-    let metadata_content = format!(
-        "Theme Name: {}\nCreated: {}\nSaved at: {}\nComponents:\n{}\n\nSuccessfully copied files:\n{}\n\nSkipped files:\n{}\n\nRuntime info:\n- USER: {}\n- HOME: {}\n- SUDO_USER: {}\n",
-        app.theme_name,
-        chrono::Utc::now().format("%Y-%m-%d %H:%M:%S UTC"),
-        display_theme_dir.display(),
-        app.checked_components()
-            .iter()
-            .map(|c| format!("- {}: {}", c.name, c.description))
-            .collect::<Vec<_>>()
-            .join("\n"),
-        if copied_files.is_empty() {
-            "No files were copied".to_string()
-        } else {
-            copied_files.iter().map(|f| format!("- {}", f)).collect::<Vec<_>>().join("\n")
-        },
-        if skipped_files.is_empty() {
-            "No files were skipped".to_string()
+    None
+}
+
+/// The Oh My Zsh theme set in `.zshrc`, the last uncommented
+/// `ZSH_THEME=` winning like it does in the shell. Empty when set to "".
+fn zsh_theme(zshrc: &str) -> Option<String> {
+    zshrc
+        .lines()
+        .rev()
+        .find_map(|line| line.trim().strip_prefix("ZSH_THEME="))
+        .map(|value| {
+            let value = value.trim();
+            match value.chars().next() {
+                Some(quote @ ('"' | '\'')) => value[1..].split(quote).next().unwrap_or(""),
+                _ => value
+                    .split(|c: char| c.is_whitespace() || c == '#')
+                    .next()
+                    .unwrap_or(""),
+            }
+            .to_string()
+        })
+}
+
+/// Oh My Zsh plugins from `plugins=(...)`, which may span several lines.
+fn zsh_plugins(zshrc: &str) -> Vec<String> {
+    let lines: Vec<&str> = zshrc.lines().collect();
+    let Some(start) = lines
+        .iter()
+        .rposition(|line| line.trim_start().starts_with("plugins=("))
+    else {
+        return Vec::new();
+    };
+
+    let mut plugins = Vec::new();
+    for (i, line) in lines[start..].iter().enumerate() {
+        let line = line.split('#').next().unwrap_or("");
+        let line = if i == 0 {
+            line.split_once('(').map_or("", |(_, rest)| rest)
         } else {
-            skipped_files.iter().map(|f| format!("- {}", f)).collect::<Vec<_>>().join("\n")
-        },
-        std::env::var("USER").unwrap_or_else(|_| "unknown".to_string()),
-        std::env::var("HOME").unwrap_or_else(|_| "unknown".to_string()),
-        std::env::var("SUDO_USER").unwrap_or_else(|_| "not set".to_string()),
-    );
-    fs::write(metadata_file, metadata_content)?;
+            line
+        };
+        let (list, closed) = match line.split_once(')') {
+            Some((list, _)) => (list, true),
+            None => (line, false),
+        };
+        plugins.extend(list.split_whitespace().map(str::to_string));
+        if closed {
+            break;
+        }
+    }
+    plugins
+}
 
-    // Clear screen and show success message
-    println!("\n{}\n", "=".repeat(60));
-    println!("🎉 THEME CREATION COMPLETE! 🎉");
-    println!("{}", "=".repeat(60));
-    println!("Theme Name: {}", app.theme_name);
-    println!("Saved at: {}", display_theme_dir.display());
-    println!("Components included: {}", app.checked_components().len());
-    println!("Files successfully copied: {}", copied_files.len());
-    if !skipped_files.is_empty() {
-        println!("Files skipped/not found: {}", skipped_files.len());
+/// Starship's config file, which `STARSHIP_CONFIG` can move out of
+/// `~/.config`. Only returned when it exists.
+fn starship_config_path() -> Option<std::path::PathBuf> {
+    env::var_os("STARSHIP_CONFIG")
+        .map(std::path::PathBuf::from)
+        .or_else(|| config_dir().map(|dir| dir.join("starship.toml")))
+        .filter(|path| path.is_file())
+}
+
+/// Prompt frameworks: Starship when its config exists, the Oh My Zsh theme
+/// and plugins set in `.zshrc`, Powerlevel10k when `.zshrc` sources its
+/// config (or the config exists).
+fn detect_prompt_theme() -> Option<Detection> {
+    let mut found = Vec::new();
+
+    if let Some(starship) = starship_config_path() {
+        found.push(Detection::file("Prompt: Starship", &starship));
     }
-    println!("{}", "=".repeat(60));
-    println!(
-        "You can find your theme at: {}",
-        display_theme_dir.display()
-    );
-    println!("A theme_info.txt file has been created with complete details.");
-    if copied_files.is_empty() {
-        println!("\n⚠️  Warning: No files were copied. Check the paths and permissions.");
-        println!("The app might be looking for files in the wrong home directory.");
+
+    let zshrc = home_dir()?.join(".zshrc");
+    let content = fs::read_to_string(&zshrc).unwrap_or_default();
+    if let Some(theme) = zsh_theme(&content) {
+        let theme = if theme.is_empty() {
+            "no theme".to_string()
+        } else {
+            theme
+        };
+        let plugins = zsh_plugins(&content);
+        let value = match plugins.len() {
+            0 => format!("Zsh: {}", theme),
+            1..=3 => format!("Zsh: {} (plugins: {})", theme, plugins.join(" ")),
+            n => format!("Zsh: {} ({} plugins)", theme, n),
+        };
+        found.push(Detection::file(value, &zshrc));
     }
-    println!("{}", "=".repeat(60));
 
-    Ok(())
+    let p10k = home_dir()?.join(".p10k.zsh");
+    let sourced = content
+        .lines()
+        .any(|line| !line.trim_start().starts_with('#') && line.contains(".p10k.zsh"));
+    if sourced {
+        found.push(Detection::file("Prompt: Powerlevel10k", &zshrc));
+    } else if p10k.is_file() {
+        found.push(Detection::file("Prompt: Powerlevel10k", &p10k));
+    }
+
+    Detection::join(found)
 }
 
-fn check_permissions(app: &App) -> Vec<PermissionIssue> {
-    let mut issues = Vec::new();
+/// The Qt widget style Plasma applications use (Breeze, Oxygen, Fusion...).
+fn detect_application_style() -> Option<Detection> {
+    let path = config_dir()?.join("kdeglobals");
+    let kdeglobals = fs::read_to_string(&path).ok();
+    let style = kdeglobals
+        .as_deref()
+        .and_then(|content| read_ini_value(content, "KDE", "widgetStyle"))
+        .map(|style| Detection::file(format!("KDE: {}", style), &path))
+        .or_else(|| {
+            kreadconfig(&["--group", "KDE", "--key", "widgetStyle"])
+                .map(|style| Detection::command(format!("KDE: {}", style), "kreadconfig"))
+        });
+
+    match style {
+        Some(style) => Some(style),
+        // The key is only written once the style was changed; which style
+        // that leaves depends on the Plasma version, so it isn't guessed
+        None if kdeglobals.is_some() => Some(Detection::file("KDE: not set", &path)),
+        None => None,
+    }
+}
 
-    for component in app.checked_components() {
-        for path_str in &component.source_paths {
-            let path = expand_tilde(path_str);
+/// The Plasma Look and Feel package, e.g. `org.kde.breezedark.desktop`.
+fn detect_global_theme() -> Option<Detection> {
+    let kdeglobals = config_dir()?.join("kdeglobals");
+    fs::read_to_string(&kdeglobals)
+        .ok()
+        .and_then(|content| read_ini_value(&content, "KDE", "LookAndFeelPackage"))
+        .map(|package| Detection::file(package, &kdeglobals))
+        .or_else(|| {
+            kreadconfig(&["--group", "KDE", "--key", "LookAndFeelPackage"])
+                .map(|package| Detection::command(package, "kreadconfig"))
+        })
+}
 
-            if path.exists() {
-                // Check read permissions
-                if !path.readable() {
-                    issues.push(PermissionIssue {
-                        component: component.name.clone(),
-                        path: path.display().to_string(),
-                        issue_type: PermissionIssueType::NoReadAccess,
-                    });
-                }
+fn detect_font_theme() -> Option<Detection> {
+    // Check font configuration
+    if let Some(output) = run_command(
+        "gsettings",
+        &["get", "org.gnome.desktop.interface", "font-name"],
+    ) {
+        if output.status.success() {
+            let font = String::from_utf8_lossy(&output.stdout);
+            let font = font.trim().trim_matches('\'');
+            return Some(Detection::command(format!("Font: {}", font), "gsettings"));
+        }
+    }
 
-                // Check if we need sudo for system directories
-                if path.starts_with("/usr") || path.starts_with("/etc") {
-                    // Try to create a temp file to test write access
-                    let test_file = path.join(".theme_creator_test");
-                    if fs::write(&test_file, "test").is_err() {
-                        let _ = fs::remove_file(&test_file); // Clean up if it was created
-                        issues.push(PermissionIssue {
-                            component: component.name.clone(),
-                            path: path.display().to_string(),
-                            issue_type: PermissionIssueType::SudoRequired,
-                        });
-                    } else {
-                        let _ = fs::remove_file(&test_file); // Clean up test file
+    // Check .fonts.conf
+    let fonts_conf = config_dir()?.join("fontconfig/fonts.conf");
+    if let Ok(content) = fs::read_to_string(&fonts_conf) {
+        for line in content.lines() {
+            if line.trim().contains("<family>") {
+                if let Some(start) = line.find("<family>") {
+                    if let Some(end) = line.find("</family>") {
+                        let font = &line[start + 8..end];
+                        let value = format!("Font: {}", font.trim());
+                        return Some(Detection::file(value, &fonts_conf));
                     }
                 }
             }
         }
     }
 
-    issues
+    None
 }
 
-fn generate_chmod_commands(issues: &[PermissionIssue]) -> String {
-    let mut commands = Vec::new();
-    let mut processed_paths = std::collections::HashSet::new();
+/// Font families the desktop is configured to use, from kdeglobals, GNOME
+/// settings and fontconfig.
+fn configured_font_families() -> Vec<String> {
+    let mut families: Vec<String> = Vec::new();
+    let Some(config) = config_dir() else {
+        return families;
+    };
+
+    // KDE stores fonts as "Family,size,..."
+    if let Ok(content) = fs::read_to_string(config.join("kdeglobals")) {
+        let keys = [
+            ("General", "font"),
+            ("General", "fixed"),
+            ("General", "smallestReadableFont"),
+            ("General", "toolBarFont"),
+            ("General", "menuFont"),
+            ("WM", "activeFont"),
+        ];
+        for (group, key) in keys {
+            if let Some(value) = read_ini_value(&content, group, key) {
+                families.extend(value.split(',').next().map(str::to_string));
+            }
+        }
+    }
+
+    // GNOME stores fonts as "Family size"
+    if let Some(output) = run_command(
+        "gsettings",
+        &["get", "org.gnome.desktop.interface", "font-name"],
+    ) {
+        if output.status.success() {
+            let font = String::from_utf8_lossy(&output.stdout);
+            let font = font.trim().trim_matches('\'');
+            let family = font
+                .rsplit_once(' ')
+                .filter(|(_, size)| size.parse::<f32>().is_ok())
+                .map_or(font, |(family, _)| family);
+            families.push(family.to_string());
+        }
+    }
 
-    for issue in issues {
-        let path = &issue.path;
-        if !processed_paths.contains(path) {
-            if path.starts_with("/usr") || path.starts_with("/etc") {
-                commands.push(format!("sudo chmod -R 755 \"{}\"", path));
-            } else {
-                commands.push(format!("chmod -R 755 \"{}\"", path));
+    if let Ok(content) = fs::read_to_string(config.join("fontconfig/fonts.conf")) {
+        for part in content.split("<family>").skip(1) {
+            if let Some((family, _)) = part.split_once("</family>") {
+                families.push(family.trim().to_string());
             }
-            processed_paths.insert(path.clone());
         }
     }
 
-    if commands.is_empty() {
-        "No chmod commands needed".to_string()
-    } else {
-        commands.join("\n")
+    let mut unique = Vec::new();
+    for family in families {
+        if !family.is_empty() && !unique.contains(&family) {
+            unique.push(family);
+        }
     }
+    unique
 }
 
-fn copy_to_clipboard(text: &str) -> Result<()> {
-    // Try xclip first (most common)
-    if Command::new("xclip")
-        .arg("-selection")
-        .arg("clipboard")
-        .stdin(std::process::Stdio::piped())
-        .spawn()
-        .and_then(|mut child| {
-            if let Some(stdin) = child.stdin.as_mut() {
-                use std::io::Write;
-                stdin.write_all(text.as_bytes())?;
-            }
-            child.wait()
-        })
-        .is_ok()
-    {
-        return Ok(());
+/// Font files of the configured families, resolved with `fc-match`. Families
+/// fontconfig substitutes with a different font are left out.
+fn configured_font_files() -> Vec<std::path::PathBuf> {
+    let mut files = Vec::new();
+    for family in configured_font_families() {
+        let Some(output) = run_command("fc-match", &["--format=%{file}\n%{family}", &family])
+        else {
+            // Without a working fontconfig nothing can be resolved
+            break;
+        };
+        if !output.status.success() {
+            continue;
+        }
+
+        let output = String::from_utf8_lossy(&output.stdout);
+        let Some((file, matched)) = output.split_once('\n') else {
+            continue;
+        };
+        let path = std::path::PathBuf::from(file.trim());
+        if matched
+            .split(',')
+            .any(|name| name.trim().eq_ignore_ascii_case(&family))
+            && path.is_file()
+            && !files.contains(&path)
+        {
+            files.push(path);
+        }
     }
+    files
+}
 
-    // Try wl-copy (Wayland)
-    if Command::new("wl-copy")
-        .stdin(std::process::Stdio::piped())
-        .spawn()
-        .and_then(|mut child| {
-            if let Some(stdin) = child.stdin.as_mut() {
-                use std::io::Write;
-                stdin.write_all(text.as_bytes())?;
-            }
-            child.wait()
-        })
-        .is_ok()
-    {
-        return Ok(());
+/// Whether Flatpak apps can see the user's GTK themes, judged from the
+/// filesystem grants in `flatpak override --user`. `None` when no Flatpak apps
+/// have been run.
+fn detect_flatpak_themes() -> Option<String> {
+    if !home_dir()?.join(".var/app").is_dir() {
+        return None;
     }
 
-    // Try xsel (alternative)
-    if Command::new("xsel")
-        .arg("--clipboard")
-        .arg("--input")
-        .stdin(std::process::Stdio::piped())
-        .spawn()
-        .and_then(|mut child| {
-            if let Some(stdin) = child.stdin.as_mut() {
-                use std::io::Write;
-                stdin.write_all(text.as_bytes())?;
+    let mut global = false;
+    let mut apps = Vec::new();
+    if let Ok(entries) = fs::read_dir(data_dir()?.join("flatpak/overrides")) {
+        for entry in entries.flatten() {
+            let Ok(content) = fs::read_to_string(entry.path()) else {
+                continue;
+            };
+            let grants_themes = read_ini_value(&content, "Context", "filesystems")
+                .is_some_and(|filesystems| grants_theme_access(&filesystems));
+            if grants_themes {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if name == "global" {
+                    global = true;
+                } else {
+                    apps.push(name);
+                }
             }
-            child.wait()
-        })
-        .is_ok()
-    {
-        return Ok(());
+        }
     }
 
-    Err(anyhow::anyhow!("No clipboard utility found"))
+    let note = if global {
+        "Flatpak apps can use your themes (global override)".to_string()
+    } else if !apps.is_empty() {
+        apps.sort();
+        format!(
+            "Only these Flatpak apps can use your themes: {}",
+            apps.join(", ")
+        )
+    } else {
+        "Flatpak apps can't see your themes; allow it with \
+         `flatpak override --user --filesystem=xdg-data/themes:ro`"
+            .to_string()
+    };
+    log::debug!("Flatpak: {}", note);
+    Some(note)
+}
+
+/// Whether a Flatpak `filesystems=` list exposes the user's theme folders.
+fn grants_theme_access(filesystems: &str) -> bool {
+    filesystems
+        .split(';')
+        .map(str::trim)
+        .filter(|grant| !grant.starts_with('!'))
+        .map(|grant| {
+            grant
+                .split(':')
+                .next()
+                .unwrap_or(grant)
+                .trim_end_matches('/')
+        })
+        .any(|grant| {
+            matches!(
+                grant,
+                "home"
+                    | "host"
+                    | "~/.themes"
+                    | "~/.local/share/themes"
+                    | "xdg-data/themes"
+                    | "xdg-config/gtk-3.0"
+                    | "xdg-config/gtk-4.0"
+            )
+        })
 }
 
-fn copy_recursive(source: &std::path::Path, destination: &std::path::Path) -> Result<()> {
-    if source.is_file() {
-        let file_name = source.file_name().context("Invalid filename")?;
-        let dest_path = destination.join(file_name);
-        fs::copy(source, dest_path)?;
-    } else if source.is_dir() {
-        let dir_name = source.file_name().context("Invalid directory name")?;
-        let dest_path = destination.join(dir_name);
-        fs_extra::dir::copy(
-            source,
-            &dest_path,
-            &fs_extra::dir::CopyOptions::new()
-                .copy_inside(true)
-                .content_only(true)
-                .overwrite(true),
-        )?;
+/// The Flatpak note, when it matters for the checked components.
+fn flatpak_summary_note(app: &App) -> Option<&str> {
+    let themes_checked = app
+        .checked_components()
+        .iter()
+        .any(|comp| comp.name == "GTK Themes");
+    app.flatpak_note.as_deref().filter(|_| themes_checked)
+}
+
+fn detect_konsole_theme() -> Option<Detection> {
+    let konsolerc = config_dir()?.join("konsolerc");
+    let content = fs::read_to_string(&konsolerc).ok()?;
+    let profile = read_ini_value(&content, "Desktop Entry", "DefaultProfile")?;
+    let name = profile.trim_end_matches(".profile");
+
+    let profile_path = data_dir()?.join("konsole").join(&profile);
+    if let Ok(profile_content) = fs::read_to_string(&profile_path) {
+        if let Some(scheme) = read_ini_value(&profile_content, "Appearance", "ColorScheme") {
+            let value = format!("Konsole: {} ({})", name, scheme);
+            return Some(Detection::file(value, &profile_path));
+        }
     }
-    Ok(())
+
+    Some(Detection::file(format!("Konsole: {}", name), &konsolerc))
 }
 
-// Style detection functions
-fn detect_gtk_theme() -> Option<String> {
-    // Check GTK3 settings
-    if let Ok(content) = fs::read_to_string(home_dir()?.join(".config/gtk-3.0/settings.ini")) {
-        for line in content.lines() {
-            if line.trim().starts_with("gtk-theme-name=") {
-                let theme = line.split('=').nth(1)?.trim().trim_matches('"');
-                return Some(format!("GTK3: {}", theme));
+fn detect_plasma_widgets() -> Option<Detection> {
+    // Count installed plasmoid packages, user ones shadowing system ones
+    let plasmoid_dirs = [
+        data_dir()?.join("plasma/plasmoids"),
+        std::path::PathBuf::from("/usr/share/plasma/plasmoids"),
+    ];
+
+    let mut installed = std::collections::HashSet::new();
+    let mut sources = Vec::new();
+    for path in &plasmoid_dirs {
+        if let Ok(entries) = fs::read_dir(path) {
+            for entry in entries.flatten() {
+                if let Ok(file_type) = entry.file_type() {
+                    if file_type.is_dir() {
+                        installed.insert(entry.file_name().to_string_lossy().to_string());
+                        if !sources.contains(&path) {
+                            sources.push(path);
+                        }
+                    }
+                }
             }
         }
     }
 
-    // Check dconf settings (requires dconf command)
-    if let Ok(output) = Command::new("gsettings")
-        .args(&["get", "org.gnome.desktop.interface", "gtk-theme"])
-        .output()
-    {
-        if output.status.success() {
-            let theme = String::from_utf8_lossy(&output.stdout);
-            let theme = theme.trim().trim_matches('\'');
-            return Some(format!("GTK: {}", theme));
+    if !installed.is_empty() {
+        let value = format!("Plasmoids: {} installed", installed.len());
+        return Detection::join(
+            sources
+                .into_iter()
+                .map(|path| Detection::dir("", path))
+                .collect(),
+        )
+        .map(|found| Detection { value, ..found });
+    }
+
+    // Fall back to the applets placed on the desktop and panels
+    let appletsrc = config_dir()?.join("plasma-org.kde.plasma.desktop-appletsrc");
+    if let Ok(content) = fs::read_to_string(&appletsrc) {
+        let mut applets = Vec::new();
+        for line in content.lines() {
+            if let Some(plugin) = line.trim().strip_prefix("plugin=") {
+                if !applets.contains(&plugin) {
+                    applets.push(plugin);
+                }
+            }
+        }
+        if !applets.is_empty() {
+            let value = format!("Plasmoids: {}", applets.join(", "));
+            return Some(Detection::file(value, &appletsrc));
         }
     }
 
     None
 }
 
-fn detect_icon_theme() -> Option<String> {
-    // Check GTK3 settings for icons
-    if let Ok(content) = fs::read_to_string(home_dir()?.join(".config/gtk-3.0/settings.ini")) {
-        for line in content.lines() {
-            if line.trim().starts_with("gtk-icon-theme-name=") {
-                let theme = line.split('=').nth(1)?.trim().trim_matches('"');
-                return Some(format!("Icons: {}", theme));
+/// The screen edge of each panel in appletsrc, in file order.
+fn panel_locations(appletsrc: &str) -> Vec<&'static str> {
+    // Containment groups are [Containments][N]; panels use org.kde.panel
+    let mut panels = Vec::new();
+    let mut in_containment = false;
+    let mut is_panel = false;
+    let mut location = None;
+    for line in appletsrc.lines().map(str::trim).chain(["[]"]) {
+        if line.starts_with('[') {
+            if is_panel {
+                panels.push(match location {
+                    Some("3") => "top",
+                    Some("4") => "bottom",
+                    Some("5") => "left",
+                    Some("6") => "right",
+                    _ => "floating",
+                });
             }
+            in_containment = line
+                .strip_prefix("[Containments][")
+                .is_some_and(|rest| rest.find(']') == Some(rest.len() - 1));
+            is_panel = false;
+            location = None;
+        } else if !in_containment {
+            continue;
+        } else if line == "plugin=org.kde.panel" {
+            is_panel = true;
+        } else if let Some(value) = line.strip_prefix("location=") {
+            location = Some(value);
         }
     }
+    panels
+}
 
-    // Check gsettings
-    if let Ok(output) = Command::new("gsettings")
-        .args(["get", "org.gnome.desktop.interface", "icon-theme"])
-        .output()
-    {
-        if output.status.success() {
-            let theme = String::from_utf8_lossy(&output.stdout);
-            let theme = theme.trim().trim_matches('\'');
-            return Some(format!("Icons: {}", theme));
+/// Where the Plasma panels sit, from the panel containments in appletsrc,
+/// and the Latte Dock layout when Latte is configured.
+fn detect_panel_layout() -> Option<Detection> {
+    let mut found = Vec::new();
+
+    let appletsrc = config_dir()?.join("plasma-org.kde.plasma.desktop-appletsrc");
+    if let Ok(content) = fs::read_to_string(&appletsrc) {
+        let mut panels = panel_locations(&content);
+        if !panels.is_empty() {
+            panels.sort();
+            found.push(Detection::file(
+                format!("Panels: {}", panels.join(", ")),
+                &appletsrc,
+            ));
         }
     }
 
-    None
+    let lattedockrc = config_dir()?.join("lattedockrc");
+    if let Ok(content) = fs::read_to_string(&lattedockrc) {
+        let layout = read_ini_value(&content, "UniversalSettings", "singleModeLayoutName")
+            .or_else(|| read_ini_value(&content, "UniversalSettings", "currentLayout"))
+            .filter(|layout| !layout.is_empty());
+        let value = match layout {
+            Some(layout) => format!("Latte Dock: {}", layout),
+            None => "Latte Dock".to_string(),
+        };
+        found.push(Detection::file(value, &lattedockrc));
+    }
+
+    Detection::join(found)
 }
 
-fn detect_cursor_theme() -> Option<String> {
-    // Check GTK3 settings for cursor theme
-    if let Ok(content) = fs::read_to_string(home_dir()?.join(".config/gtk-3.0/settings.ini")) {
-        for line in content.lines() {
-            if line.trim().starts_with("gtk-cursor-theme-name=") {
-                let theme = line.split('=').nth(1)?.trim().trim_matches('"');
-                return Some(format!("Cursor: {}", theme));
-            }
+fn detect_wallpaper() -> Option<Detection> {
+    let appletsrc = config_dir()?.join("plasma-org.kde.plasma.desktop-appletsrc");
+    let slideshow = wallpaper_slideshow_paths();
+    match slideshow.as_slice() {
+        [] => {}
+        [folder] => {
+            let name = folder.file_name()?.to_string_lossy().to_string();
+            return Some(Detection::file(format!("Slideshow: {}", name), &appletsrc));
+        }
+        folders => {
+            let value = format!("Slideshow: {} folders", folders.len());
+            return Some(Detection::file(value, &appletsrc));
         }
     }
 
-    // Check gsettings
-    if let Ok(output) = Command::new("gsettings")
-        .args(["get", "org.gnome.desktop.interface", "cursor-theme"])
-        .output()
-    {
-        if output.status.success() {
-            let theme = String::from_utf8_lossy(&output.stdout);
-            let theme = theme.trim().trim_matches('\'');
-            return Some(format!("Cursor: {}", theme));
+    let path = active_wallpaper_path()?;
+    let name = path.file_name()?.to_string_lossy().to_string();
+    Some(Detection::file(format!("Wallpaper: {}", name), &appletsrc))
+}
+
+/// The folders of desktops using the slideshow wallpaper plugin, from their
+/// `SlidePaths=` key. Folders that no longer exist are left out.
+fn wallpaper_slideshow_paths() -> Vec<std::path::PathBuf> {
+    let Some(content) = config_dir().and_then(|dir| {
+        fs::read_to_string(dir.join("plasma-org.kde.plasma.desktop-appletsrc")).ok()
+    }) else {
+        return Vec::new();
+    };
+
+    // Containment ids using the plugin, and the slide paths of every containment
+    let mut slideshow_ids = Vec::new();
+    let mut slide_paths = Vec::new();
+    let mut group = String::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            group = line.to_string();
+            continue;
+        }
+        let Some(id) = group
+            .strip_prefix("[Containments][")
+            .and_then(|rest| rest.split(']').next())
+        else {
+            continue;
+        };
+        let is_containment = group == format!("[Containments][{}]", id);
+        if is_containment && line == "wallpaperplugin=org.kde.slideshow" {
+            slideshow_ids.push(id.to_string());
+        } else if group.ends_with("[Wallpaper][org.kde.slideshow][General]") {
+            if let Some(paths) = line.strip_prefix("SlidePaths=") {
+                slide_paths.push((id.to_string(), paths.to_string()));
+            }
         }
     }
 
-    // Check icon theme directories for cursor themes
-    let icon_paths = [
-        home_dir()?.join(".icons"),
-        home_dir()?.join(".local/share/icons"),
-        std::path::PathBuf::from("/usr/share/icons"),
-    ];
+    let mut folders = Vec::new();
+    for (id, paths) in slide_paths {
+        if !slideshow_ids.contains(&id) {
+            continue;
+        }
+        for path in paths.split(',') {
+            let path = path.trim();
+            let path = path.strip_prefix("file://").unwrap_or(path);
+            let folder = expand_tilde(path.trim_end_matches('/'));
+            if !path.is_empty() && folder.is_dir() && !folders.contains(&folder) {
+                folders.push(folder);
+            }
+        }
+    }
+    folders
+}
 
-    for path in &icon_paths {
-        if path.exists() {
-            if let Ok(entries) = fs::read_dir(path) {
-                for entry in entries.flatten() {
-                    if let Ok(file_type) = entry.file_type() {
-                        if file_type.is_dir() {
-                            let dir_name_owned = entry.file_name().to_string_lossy().to_string();
-                            let dir_name = dir_name_owned.as_str();
-                            if dir_name.to_lowercase().contains("cursor") {
-                                return Some(format!("Cursor: {}", dir_name));
-                            }
-                        }
-                    }
+/// Resolves the `Image=` key of the desktop containment's image wallpaper
+/// plugin to an absolute path.
+fn active_wallpaper_path() -> Option<std::path::PathBuf> {
+    let content =
+        fs::read_to_string(config_dir()?.join("plasma-org.kde.plasma.desktop-appletsrc")).ok()?;
+
+    let mut in_wallpaper_group = false;
+    for line in content.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_wallpaper_group = line.starts_with("[Containments]")
+                && line.ends_with("[Wallpaper][org.kde.image][General]");
+        } else if in_wallpaper_group {
+            if let Some(image) = line.strip_prefix("Image=") {
+                let image = image.trim();
+                let image = image.strip_prefix("file://").unwrap_or(image);
+                if image.is_empty() {
+                    continue;
+                }
+                let path = expand_tilde(image);
+                if path.exists() {
+                    return Some(path);
                 }
             }
         }
@@ -1015,419 +7910,799 @@ fn detect_cursor_theme() -> Option<String> {
     None
 }
 
-fn detect_qt_style() -> Option<String> {
-    // Check qt5ct
-    if let Ok(content) = fs::read_to_string(home_dir()?.join(".config/qt5ct/qt5ct.conf")) {
-        for line in content.lines() {
-            if line.trim().starts_with("style=") {
-                let style = line.split('=').nth(1)?.trim();
-                return Some(format!("Qt5: {}", style));
-            }
-        }
+fn detect_launcher_theme() -> Option<Detection> {
+    if let Some(theme) = rofi_theme_name() {
+        let name = std::path::Path::new(&theme)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or(theme);
+        let rofi_config = config_dir()?.join("rofi/config.rasi");
+        return Some(Detection::file(format!("Rofi: {}", name), &rofi_config));
     }
 
-    // Check qt6ct
-    if let Ok(content) = fs::read_to_string(home_dir()?.join(".config/qt6ct/qt6ct.conf")) {
-        for line in content.lines() {
-            if line.trim().starts_with("style=") {
-                let style = line.split('=').nth(1)?.trim();
-                return Some(format!("Qt6: {}", style));
-            }
-        }
+    let wofi_dir = config_dir()?.join("wofi");
+    if wofi_dir.join("style.css").exists() {
+        return Some(Detection::file(
+            "Wofi: custom style",
+            &wofi_dir.join("style.css"),
+        ));
+    }
+    if wofi_dir.exists() {
+        return Some(Detection::dir("Wofi: configured", &wofi_dir));
     }
 
     None
 }
 
-fn detect_color_scheme() -> Option<String> {
-    // Check KDE color schemes
-    if let Ok(content) = fs::read_to_string(home_dir()?.join(".config/kdeglobals")) {
-        for line in content.lines() {
-            if line.trim().starts_with("ColorScheme=") {
-                let scheme = line.split('=').nth(1)?.trim();
-                return Some(format!("KDE: {}", scheme));
-            }
-        }
-    }
+fn detect_notification_theme() -> Option<Detection> {
+    let config = config_dir()?;
+    let daemons: Vec<Detection> = [("Dunst", "dunst/dunstrc"), ("Mako", "mako/config")]
+        .into_iter()
+        .filter(|(_, file)| config.join(file).exists())
+        .map(|(name, file)| Detection::file(name, &config.join(file)))
+        .collect();
 
-    // Check Plasma colors
-    if let Ok(output) = Command::new("kreadconfig5")
-        .args(&["--group", "Colors:Window", "--key", "BackgroundNormal"])
-        .output()
+    Detection::join(daemons).map(|found| Detection {
+        value: format!("{}: configured", found.value),
+        ..found
+    })
+}
+
+fn detect_bar_theme() -> Option<Detection> {
+    let config = config_dir()?;
+    let mut bars = Vec::new();
+
+    if config.join("waybar/style.css").exists() {
+        bars.push(Detection::file("Waybar", &config.join("waybar/style.css")));
+    }
+    // Polybar still reads the extension-less `config` of older setups
+    if let Some(file) = ["polybar/config.ini", "polybar/config"]
+        .iter()
+        .map(|file| config.join(file))
+        .find(|file| file.exists())
     {
-        if output.status.success() {
-            let color_str = String::from_utf8_lossy(&output.stdout);
-            let color = color_str.trim();
-            return Some(format!("Plasma: {}", color));
-        }
+        bars.push(Detection::file("Polybar", &file));
     }
 
-    None
+    Detection::join(bars).map(|found| Detection {
+        value: format!("{}: configured", found.value),
+        ..found
+    })
 }
 
-fn detect_window_decorations() -> Option<String> {
-    // Check KDE KWin window decorations
-    if let Ok(output) = Command::new("kreadconfig5")
-        .args(&["--group", "org.kde.kdecoration2", "--key", "library"])
-        .output()
+/// The argument of the `@theme "..."` line in rofi's `config.rasi`.
+fn rofi_theme_name() -> Option<String> {
+    let content = fs::read_to_string(config_dir()?.join("rofi/config.rasi")).ok()?;
+    content.lines().find_map(|line| {
+        let theme = line.trim().strip_prefix("@theme")?;
+        let theme = theme.trim().trim_end_matches(';').trim().trim_matches('"');
+        (!theme.is_empty()).then(|| theme.to_string())
+    })
+}
+
+/// Resolves the rofi theme the way rofi does: a path as-is, otherwise a name
+/// looked up in the user and system theme directories.
+fn rofi_theme_path() -> Option<std::path::PathBuf> {
+    let theme = rofi_theme_name()?;
+    if theme.starts_with('/') || theme.starts_with('~') {
+        let path = expand_tilde(&theme);
+        return path.is_file().then_some(path);
+    }
+
+    let file_name = if theme.ends_with(".rasi") {
+        theme
+    } else {
+        format!("{}.rasi", theme)
+    };
+    [
+        config_dir()?.join("rofi/themes"),
+        config_dir()?.join("rofi"),
+        data_dir()?.join("rofi/themes"),
+        std::path::PathBuf::from("/usr/share/rofi/themes"),
+    ]
+    .iter()
+    .map(|dir| dir.join(&file_name))
+    .find(|path| path.is_file())
+}
+
+/// The default Konsole profile and the color scheme it uses, which may live in
+/// the system-wide `/usr/share/konsole/` rather than the user's data directory.
+fn konsole_referenced_files() -> Vec<std::path::PathBuf> {
+    let mut files = Vec::new();
+    let (Some(config), Some(data)) = (config_dir(), data_dir()) else {
+        return files;
+    };
+
+    let Some(profile) = fs::read_to_string(config.join("konsolerc"))
+        .ok()
+        .and_then(|content| read_ini_value(&content, "Desktop Entry", "DefaultProfile"))
+    else {
+        return files;
+    };
+
+    let search_dirs = [
+        data.join("konsole"),
+        std::path::PathBuf::from("/usr/share/konsole"),
+    ];
+
+    let Some(profile_path) = search_dirs
+        .iter()
+        .map(|dir| dir.join(&profile))
+        .find(|path| path.is_file())
+    else {
+        return files;
+    };
+
+    if let Some(scheme) = fs::read_to_string(&profile_path)
+        .ok()
+        .and_then(|content| read_ini_value(&content, "Appearance", "ColorScheme"))
     {
-        if output.status.success() {
-            let deco_str = String::from_utf8_lossy(&output.stdout);
-            let decoration = deco_str.trim();
-            if !decoration.is_empty() && decoration != "org.kde.kwin.aurorae" {
-                return Some(format!("KWin: {}", decoration));
-            }
+        let scheme_file = format!("{}.colorscheme", scheme);
+        if let Some(scheme_path) = search_dirs
+            .iter()
+            .map(|dir| dir.join(&scheme_file))
+            .find(|path| path.is_file())
+        {
+            files.push(scheme_path);
         }
     }
 
-    // Check KWin config directly
-    if let Ok(content) = fs::read_to_string(home_dir()?.join(".config/kwinrc")) {
-        for line in content.lines() {
-            if line.trim().starts_with("plugin=") {
-                let plugin = line.split('=').nth(1)?.trim();
-                return Some(format!("KWin Plugin: {}", plugin));
+    files.push(profile_path);
+    files
+}
+
+/// How long a detection command may run before it is given up on.
+const COMMAND_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Programs that timed out once and are skipped for the rest of the run.
+static HUNG_COMMANDS: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+/// Runs `program` and collects its output. Returns `None` when it is missing,
+/// can't be started or doesn't finish within `COMMAND_TIMEOUT`, so a broken or
+/// hanging tool only costs its own detection (and only once).
+fn run_command(program: &str, args: &[&str]) -> Option<std::process::Output> {
+    let hung = HUNG_COMMANDS
+        .lock()
+        .is_ok_and(|hung| hung.iter().any(|name| name == program));
+    if hung {
+        return None;
+    }
+
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .inspect_err(|e| log::debug!("{} not run: {}", program, e))
+        .ok()?;
+
+    // Read on a thread so a chatty command can't fill the pipe and stall
+    let mut stdout = child.stdout.take()?;
+    let reader = std::thread::spawn(move || {
+        let mut buffer = Vec::new();
+        io::Read::read_to_end(&mut stdout, &mut buffer).map(|_| buffer)
+    });
+
+    let status = match child.wait_timeout(COMMAND_TIMEOUT) {
+        Ok(Some(status)) => status,
+        _ => {
+            let _ = child.kill();
+            let _ = child.wait();
+            log::warn!(
+                "{} {} timed out after {:?}",
+                program,
+                args.join(" "),
+                COMMAND_TIMEOUT
+            );
+            if let Ok(mut hung) = HUNG_COMMANDS.lock() {
+                hung.push(program.to_string());
             }
+            return None;
         }
-    }
+    };
+    let stdout = reader.join().ok()?.ok()?;
+    Some(std::process::Output {
+        status,
+        stdout,
+        stderr: Vec::new(),
+    })
+}
 
-    // Check for AwesomeWM decorations
-    if let Ok(content) = fs::read_to_string(home_dir()?.join(".config/awesome/rc.lua")) {
-        for line in content.lines() {
-            if line.trim().contains("beautiful.init") {
-                return Some("AwesomeWM: Beautiful".into());
+/// Runs the `kreadconfig` of the running Plasma version, falling back to the
+/// other one when it is missing or fails. Returns the trimmed value, or
+/// `None` when it is empty.
+fn kreadconfig(args: &[&str]) -> Option<String> {
+    let binaries = if plasma_major_version() == Some(5) {
+        ["kreadconfig5", "kreadconfig6"]
+    } else {
+        ["kreadconfig6", "kreadconfig5"]
+    };
+    for binary in binaries {
+        if let Some(output) = run_command(binary, args) {
+            if output.status.success() {
+                let value = String::from_utf8_lossy(&output.stdout).trim().to_string();
+                return (!value.is_empty()).then_some(value);
             }
         }
     }
+    None
+}
 
-    // Check for Openbox theme
-    if let Ok(content) = fs::read_to_string(home_dir()?.join(".config/openbox/rc.xml")) {
-        for line in content.lines() {
-            if line.trim().contains("<theme>") {
-                if let Some(start) = line.find("<name>") {
-                    if let Some(end) = line.find("</name>") {
-                        let theme = &line[start + 6..end];
-                        return Some(format!("Openbox: {}", theme.trim()));
-                    }
+/// Byte offset of the first ASCII case-insensitive occurrence of `needle`.
+fn find_ignore_case(haystack: &str, needle: &str) -> Option<usize> {
+    if needle.is_empty() {
+        return Some(0);
+    }
+    haystack.char_indices().map(|(i, _)| i).find(|&i| {
+        haystack
+            .get(i..i + needle.len())
+            .is_some_and(|candidate| candidate.eq_ignore_ascii_case(needle))
+    })
+}
+
+/// Reads `key` from `[group]` in an INI-style (KConfig, XDG desktop) file.
+fn read_ini_value(content: &str, group: &str, key: &str) -> Option<String> {
+    let mut in_group = false;
+    for line in content.lines() {
+        let line = line.trim();
+        if line.starts_with('[') && line.ends_with(']') {
+            in_group = &line[1..line.len() - 1] == group;
+        } else if in_group {
+            if let Some((k, v)) = line.split_once('=') {
+                if k.trim() == key {
+                    return Some(v.trim().to_string());
                 }
             }
         }
     }
-
     None
 }
 
-fn detect_splash_screen() -> Option<String> {
-    // Check Plymouth (boot splash)
-    if let Ok(output) = Command::new("plymouth-set-default-theme")
-        .arg("--show-current")
-        .output()
-    {
-        if output.status.success() {
-            let theme_str = String::from_utf8_lossy(&output.stdout);
-            let theme = theme_str.trim();
-            if !theme.is_empty() {
-                return Some(format!("Plymouth: {}", theme));
-            }
+trait PathExt {
+    fn readable(&self) -> bool;
+}
+
+impl PathExt for Path {
+    fn readable(&self) -> bool {
+        // Actually opening the path is the only check that accounts for
+        // ownership, groups, ACLs and the process' effective uid
+        if self.is_dir() {
+            fs::read_dir(self).is_ok()
+        } else {
+            fs::File::open(self).is_ok()
         }
     }
+}
 
-    // Check Plymouth config
-    if let Ok(content) = fs::read_to_string("/etc/plymouth/plymouthd.conf") {
-        for line in content.lines() {
-            if line.trim().starts_with("Theme=") {
-                let theme = line.split('=').nth(1)?.trim();
-                return Some(format!("Plymouth: {}", theme));
+/// Folders searched for icon and cursor themes, in lookup order.
+fn icon_theme_dirs() -> Vec<std::path::PathBuf> {
+    let data_dirs = env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_string());
+    [
+        expand_tilde("~/.icons"),
+        expand_tilde("~/.local/share/icons"),
+    ]
+    .into_iter()
+    .chain(
+        data_dirs
+            .split(':')
+            .filter(|dir| !dir.is_empty())
+            .map(|dir| Path::new(dir).join("icons")),
+    )
+    .chain([std::path::PathBuf::from("/usr/share/pixmaps")])
+    .collect()
+}
+
+/// The parts of the icon theme `theme_dir` that make up its cursor theme.
+fn cursor_theme_files(theme_dir: &Path) -> Vec<std::path::PathBuf> {
+    ["cursors", "index.theme", "cursor.theme"]
+        .map(|name| theme_dir.join(name))
+        .into_iter()
+        .filter(|entry| entry.exists())
+        .collect()
+}
+
+/// The themes `theme_dir` inherits from, per its index.theme.
+fn theme_inherits(theme_dir: &Path) -> Vec<String> {
+    fs::read_to_string(theme_dir.join("index.theme"))
+        .ok()
+        .and_then(|content| read_ini_value(&content, "Icon Theme", "Inherits"))
+        .map(|inherits| {
+            inherits
+                .split(',')
+                .map(|name| name.trim().to_string())
+                .filter(|name| !name.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Resolves a configured component path: `$VAR` and `${VAR}` references,
+/// then the rest as `expand_tilde` does.
+fn expand_path(path: &str) -> std::path::PathBuf {
+    expand_tilde(&expand_vars(path))
+}
+
+/// Resolves `~` as the home of the user being exported, and relative paths
+/// against the current directory. `$` is kept as written, since paths read
+/// from other programs' files may contain it literally.
+fn expand_tilde(path: &str) -> std::path::PathBuf {
+    for (prefix, var) in [
+        ("~/.config", "XDG_CONFIG_HOME"),
+        ("~/.local/share", "XDG_DATA_HOME"),
+        ("~/.cache", "XDG_CACHE_HOME"),
+    ] {
+        if let Some(rest) = path.strip_prefix(prefix) {
+            if rest.is_empty() || rest.starts_with('/') {
+                if let Some(dir) = xdg_env_dir(var) {
+                    return dir.join(rest.trim_start_matches('/'));
+                }
             }
         }
     }
 
-    // Check GRUB themes
-    if let Ok(content) = fs::read_to_string("/etc/default/grub") {
-        for line in content.lines() {
-            if line.trim().starts_with("GRUB_THEME=") {
-                let theme = line.split('=').nth(1)?.trim().trim_matches('"');
-                return Some(format!("GRUB: {}", theme));
-            }
-        }
+    if let Some(rest) = path.strip_prefix("~/") {
+        // Get the real user's home directory
+        let home = get_user_home_dir();
+        return home.join(rest);
+    } else if path == "~" {
+        let home = get_user_home_dir();
+        return home;
     }
 
-    // Check for available splash themes
-    if std::path::Path::new("/usr/share/plymouth/themes").exists() {
-        if let Ok(entries) = fs::read_dir("/usr/share/plymouth/themes") {
-            for entry in entries.flatten() {
-                if let Ok(file_type) = entry.file_type() {
-                    if file_type.is_dir() {
-                        return Some("Plymouth: Available".into());
-                    }
-                }
-            }
+    // Handle relative paths by making them absolute to current directory
+    let path_buf = std::path::PathBuf::from(path);
+    if path_buf.is_relative() {
+        if let Ok(current_dir) = std::env::current_dir() {
+            return current_dir.join(path_buf);
         }
     }
 
-    None
+    path_buf
 }
 
-fn detect_sddm_theme() -> Option<String> {
-    // Check current SDDM theme
-    if let Ok(content) = fs::read_to_string("/etc/sddm.conf") {
-        for line in content.lines() {
-            if line.trim().starts_with("Current=") {
-                let theme = line.split('=').nth(1)?.trim();
-                return Some(format!("SDDM: {}", theme));
+/// Replaces `$VAR` and `${VAR}` in `path`. `HOME` and the XDG base
+/// directories resolve like `~` does, so they follow `--home` and sudo;
+/// other variables come from the environment and unknown ones stay as
+/// written.
+fn expand_vars(path: &str) -> String {
+    let mut expanded = String::new();
+    let mut rest = path;
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let (name, len) = match after.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) => (&braced[..end], end + 2),
+                None => ("", 0),
+            },
+            None => {
+                let end = after
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(after.len());
+                (&after[..end], end)
             }
+        };
+        let reference = &rest[start..start + 1 + len];
+        match path_var(name) {
+            Some(value) => expanded.push_str(&value),
+            None => expanded.push_str(reference),
         }
+        rest = &rest[start + 1 + len..];
     }
+    expanded.push_str(rest);
+    expanded
+}
 
-    // Check in sddm.conf.d
-    if let Ok(entries) = fs::read_dir("/etc/sddm.conf.d") {
-        for entry in entries.flatten() {
-            if let Ok(content) = fs::read_to_string(entry.path()) {
-                for line in content.lines() {
-                    if line.trim().starts_with("Current=") {
-                        let theme = line.split('=').nth(1)?.trim();
-                        return Some(format!("SDDM: {}", theme));
-                    }
-                }
-            }
+/// The value of the variable `name` for `expand_vars`.
+fn path_var(name: &str) -> Option<String> {
+    let dir = match name {
+        "" => return None,
+        "HOME" => Some(get_user_home_dir()),
+        "XDG_CONFIG_HOME" => config_dir(),
+        "XDG_DATA_HOME" => data_dir(),
+        "XDG_CACHE_HOME" => {
+            xdg_env_dir("XDG_CACHE_HOME").or_else(|| Some(home_dir()?.join(".cache")))
         }
-    }
+        _ => return env::var(name).ok(),
+    };
+    dir.map(|dir| dir.display().to_string())
+}
 
-    None
+/// Writes every record to `~/.cache/kde-copycat/last-run.log` and echoes
+/// errors (everything with `--verbose`) to stderr while no TUI is shown.
+struct RunLogger {
+    file: Option<std::sync::Mutex<fs::File>>,
+    console_level: log::LevelFilter,
+    console: std::sync::atomic::AtomicBool,
 }
 
-fn detect_terminal_theme() -> Option<String> {
-    // Check alacritty
-    if let Ok(content) = fs::read_to_string(home_dir()?.join(".config/alacritty/alacritty.yml")) {
-        for line in content.lines() {
-            if line.trim().starts_with("colors:") || line.trim().contains("primary:") {
-                return Some("Alacritty: Custom theme".into());
-            }
-        }
+static LOGGER: std::sync::OnceLock<RunLogger> = std::sync::OnceLock::new();
+
+impl log::Log for RunLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.target().starts_with(module_path!())
     }
 
-    // Check kitty
-    if let Ok(content) = fs::read_to_string(home_dir()?.join(".config/kitty/kitty.conf")) {
-        for line in content.lines() {
-            if line.trim().starts_with("include") && line.contains("theme") {
-                let theme = line.split_whitespace().nth(1)?;
-                return Some(format!("Kitty: {}", theme));
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        if let Some(file) = &self.file {
+            if let Ok(mut file) = file.lock() {
+                let _ = io::Write::write_fmt(
+                    &mut *file,
+                    format_args!(
+                        "{} {:<5} {}\n",
+                        chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
+                        record.level(),
+                        record.args()
+                    ),
+                );
             }
         }
-    }
-
-    // Check gnome-terminal
-    if let Ok(output) = Command::new("gsettings")
-        .args(&[
-            "get",
-            "org.gnome.Terminal.Profiles:/org/gnome/terminal/legacy/profiles:/",
-            "default-profile",
-        ])
-        .output()
-    {
-        if output.status.success() {
-            return Some("GNOME Terminal: Configured".into());
+        if record.level() <= self.console_level
+            && self.console.load(std::sync::atomic::Ordering::Relaxed)
+        {
+            eprintln!(
+                "{}: {}",
+                record.level().as_str().to_lowercase(),
+                record.args()
+            );
         }
     }
 
-    None
+    fn flush(&self) {}
 }
 
-fn detect_wm_theme() -> Option<String> {
-    // Check current window manager
-    if let Ok(desktop) = std::env::var("XDG_CURRENT_DESKTOP") {
-        if !desktop.is_empty() {
-            return Some(format!("WM: {}", desktop));
-        }
+fn log_path() -> std::path::PathBuf {
+    if USER_OVERRIDE.get().is_some() {
+        return own_home_dir()
+            .unwrap_or_default()
+            .join(".cache/kde-copycat/last-run.log");
     }
+    expand_tilde("~/.cache/kde-copycat/last-run.log")
+}
 
-    // Check for specific window managers
-    if std::env::var("I3SOCK").is_ok() {
-        return Some("WM: i3".into());
+/// Starts logging to `log_path()`. Without a writable log file only the
+/// console output remains.
+fn init_logging(verbose: bool) {
+    let path = log_path();
+    // A sudo re-run passes --home and appends to the log of the run that
+    // started it
+    if HOME_OVERRIDE.get().is_none() || USER_OVERRIDE.get().is_some() {
+        if let Some(dir) = path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        let _ = fs::File::create(&path);
     }
-
-    if std::env::var("BSPWM_SOCKET").is_ok() {
-        return Some("WM: bspwm".into());
+    let file = fs::OpenOptions::new().create(true).append(true).open(&path);
+    if file.is_ok() {
+        give_to_sudo_user(&path);
     }
 
-    // Check processes
-    if let Ok(output) = Command::new("ps")
-        .args(&["-u", std::env::var("USER").unwrap_or_default().as_str()])
-        .output()
-    {
-        let output_str = String::from_utf8_lossy(&output.stdout);
-        if output_str.contains("openbox") {
-            return Some("WM: Openbox".into());
-        }
-        if output_str.contains("xfwm4") {
-            return Some("WM: Xfwm4".into());
-        }
-        if output_str.contains("kwin") {
-            return Some("WM: KWin".into());
-        }
+    let logger = LOGGER.get_or_init(|| RunLogger {
+        file: file.ok().map(std::sync::Mutex::new),
+        console_level: if verbose {
+            log::LevelFilter::Debug
+        } else {
+            log::LevelFilter::Error
+        },
+        console: std::sync::atomic::AtomicBool::new(true),
+    });
+    if log::set_logger(logger).is_ok() {
+        log::set_max_level(log::LevelFilter::Debug);
     }
+}
 
-    None
+fn set_console_logging(enabled: bool) {
+    if let Some(logger) = LOGGER.get() {
+        logger
+            .console
+            .store(enabled, std::sync::atomic::Ordering::Relaxed);
+    }
 }
 
-fn detect_shell_theme() -> Option<String> {
-    // Check current shell
-    if let Ok(shell) = std::env::var("SHELL") {
-        if shell.contains("zsh") {
-            if let Ok(content) = fs::read_to_string(home_dir()?.join(".zshrc")) {
-                if content.contains("ZSH_THEME=") {
-                    return Some("Shell: Zsh (Oh My Zsh)".into());
-                }
-                return Some("Shell: Zsh".into());
-            }
-        } else if shell.contains("bash") {
-            return Some("Shell: Bash".into());
-        } else if shell.contains("fish") {
-            return Some("Shell: Fish".into());
+/// Hands a file created under sudo back to the invoking user, so root doesn't
+/// leave files in their home they can't replace.
+fn give_to_sudo_user(path: &Path) {
+    let id = |var| env::var(var).ok()?.parse::<u32>().ok();
+    if let (Some(uid), Some(gid)) = (id("SUDO_UID"), id("SUDO_GID")) {
+        if let Some(dir) = path.parent() {
+            let _ = std::os::unix::fs::chown(dir, Some(uid), Some(gid));
         }
+        let _ = std::os::unix::fs::chown(path, Some(uid), Some(gid));
     }
+}
 
-    None
+/// Home directory handed over on the command line (`--home`), used instead
+/// of any detection. Set by the sudo re-run so root reads the user's files.
+static HOME_OVERRIDE: std::sync::OnceLock<std::path::PathBuf> = std::sync::OnceLock::new();
+
+/// Account passed with `--user`, whose home `HOME_OVERRIDE` then points to.
+static USER_OVERRIDE: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+
+/// The home `find_user_home` settled on and where it came from, looked up
+/// once per run.
+static USER_HOME: std::sync::OnceLock<Option<(std::path::PathBuf, &'static str)>> =
+    std::sync::OnceLock::new();
+
+/// The graphical session kde-copycat runs in, which decides whether X11-only
+/// settings like X resources apply.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SessionType {
+    Wayland,
+    X11,
+    /// A text console or an environment without a display, e.g. under sudo.
+    Unknown,
 }
 
-fn detect_application_style() -> Option<String> {
-    // First check if KDE style is set (Oxygen, Breeze, etc.)
-    if let Ok(output) = Command::new("kreadconfig5")
-        .args(&["--group", "KDE", "--key", "style"])
-        .output()
-    {
-        if output.status.success() {
-            let style_str = String::from_utf8_lossy(&output.stdout);
-            let style = style_str.trim();
-            if !style.is_empty() && style != "default" {
-                return Some(format!("KDE Style: {}", style));
-            }
+impl SessionType {
+    pub fn label(self) -> Option<&'static str> {
+        match self {
+            Self::Wayland => Some("Wayland"),
+            Self::X11 => Some("X11"),
+            Self::Unknown => None,
         }
     }
+}
 
-    // Check for KDE global theme (which includes application style)
-    if let Ok(output) = Command::new("kreadconfig5")
-        .args(&["--group", "General", "--key", "ColorSchemeKey"])
-        .output()
-    {
-        if output.status.success() {
-            let color_scheme_str = String::from_utf8_lossy(&output.stdout);
-            let color_scheme = color_scheme_str.trim();
-            if !color_scheme.is_empty() {
-                return Some(format!("KDE Theme: {}", color_scheme));
-            }
-        }
+/// `XDG_SESSION_TYPE` as set by the login manager, falling back to which
+/// display variables are set.
+fn detect_session_type() -> SessionType {
+    match env::var("XDG_SESSION_TYPE").as_deref() {
+        Ok("wayland") => return SessionType::Wayland,
+        Ok("x11") => return SessionType::X11,
+        _ => {}
     }
-
-    // Check GTK theme as fallback (since it controls application styling)
-    if let Ok(output) = Command::new("gsettings")
-        .args(&["get", "org.gnome.desktop.interface", "gtk-theme"])
-        .output()
-    {
-        if output.status.success() {
-            let theme = String::from_utf8_lossy(&output.stdout);
-            let theme = theme.trim().trim_matches('\'');
-            if !theme.is_empty() && theme != "Adwaita" {
-                return Some(format!("GTK Style: {}", theme));
-            }
-        }
+    if env::var_os("WAYLAND_DISPLAY").is_some_and(|display| !display.is_empty()) {
+        SessionType::Wayland
+    } else if env::var_os("DISPLAY").is_some_and(|display| !display.is_empty()) {
+        SessionType::X11
+    } else {
+        SessionType::Unknown
     }
+}
 
-    // Fallback: detect what toolkits are available
-    let mut toolkits = Vec::new();
+static SESSION_TYPE: std::sync::OnceLock<SessionType> = std::sync::OnceLock::new();
 
-    if home_dir()?.join(".config/gtk-3.0/settings.ini").exists() {
-        toolkits.push("GTK3");
-    }
+fn session_type() -> SessionType {
+    *SESSION_TYPE.get_or_init(|| {
+        let session = detect_session_type();
+        log::info!("Session type: {:?}", session);
+        session
+    })
+}
 
-    if home_dir()?.join(".config/qt5ct/qt5ct.conf").exists() {
-        toolkits.push("Qt5");
-    }
+/// The installed Plasma version from `plasmashell --version`, e.g. "6.0.4",
+/// or only the major version from `KDE_SESSION_VERSION` when plasmashell
+/// can't be run.
+fn detect_plasma_version() -> Option<String> {
+    let from_plasmashell = run_command("plasmashell", &["--version"])
+        .filter(|output| output.status.success())
+        .and_then(|output| {
+            String::from_utf8_lossy(&output.stdout)
+                .split_whitespace()
+                .find(|word| word.starts_with(|c: char| c.is_ascii_digit()))
+                .map(str::to_string)
+        });
+    from_plasmashell.or_else(|| {
+        env::var("KDE_SESSION_VERSION")
+            .ok()
+            .filter(|version| !version.is_empty())
+    })
+}
 
-    if home_dir()?.join(".config/qt6ct/qt6ct.conf").exists() {
-        toolkits.push("Qt6");
-    }
+static PLASMA_VERSION: std::sync::OnceLock<Option<String>> = std::sync::OnceLock::new();
 
-    if !toolkits.is_empty() {
-        return Some(format!("Available: {}", toolkits.join(", ")));
-    }
+fn plasma_version() -> Option<&'static str> {
+    PLASMA_VERSION
+        .get_or_init(|| {
+            let version = detect_plasma_version();
+            log::info!("Plasma version: {}", version.as_deref().unwrap_or("none"));
+            version
+        })
+        .as_deref()
+}
 
-    Some("Default".to_string())
+/// 5 or 6, deciding between the `kreadconfig5` and `kreadconfig6` tools.
+fn plasma_major_version() -> Option<u32> {
+    plasma_version()?.split('.').next()?.parse().ok()
 }
 
-fn detect_font_theme() -> Option<String> {
-    // Check font configuration
-    if let Ok(output) = Command::new("gsettings")
-        .args(&["get", "org.gnome.desktop.interface", "font-name"])
-        .output()
-    {
-        if output.status.success() {
-            let font = String::from_utf8_lossy(&output.stdout);
-            let font = font.trim().trim_matches('\'');
-            return Some(format!("Font: {}", font));
-        }
-    }
+/// Whether to draw with `ASCII_GLYPHS`, set once from `--ascii` and the locale.
+static ASCII_OUTPUT: std::sync::OnceLock<bool> = std::sync::OnceLock::new();
+
+/// Symbols used in the TUI and the reports, so terminals without Unicode get
+/// ASCII stand-ins instead of mojibake.
+pub struct Glyphs {
+    pub folder: &'static str,
+    pub file: &'static str,
+    pub check: &'static str,
+    pub cross: &'static str,
+    pub warning: &'static str,
+    pub arrow: &'static str,
+    pub up_down: &'static str,
+    pub shift_up_down: &'static str,
+    pub busy: &'static str,
+    pub done: &'static str,
+    pub ellipsis: &'static str,
+    pub border: symbols::border::Set,
+    pub scrollbar: symbols::scrollbar::Set,
+}
 
-    // Check .fonts.conf
-    if let Ok(content) = fs::read_to_string(home_dir()?.join(".config/fontconfig/fonts.conf")) {
-        for line in content.lines() {
-            if line.trim().contains("<family>") {
-                if let Some(start) = line.find("<family>") {
-                    if let Some(end) = line.find("</family>") {
-                        let font = &line[start + 8..end];
-                        return Some(format!("Font: {}", font.trim()));
-                    }
-                }
-            }
-        }
+const UNICODE_GLYPHS: Glyphs = Glyphs {
+    folder: "📁",
+    file: "📄",
+    check: "✓",
+    cross: "❌",
+    warning: "⚠",
+    arrow: "→",
+    up_down: "↑ ↓",
+    shift_up_down: "Shift+↑ ↓",
+    busy: "🔍",
+    done: "🎉",
+    ellipsis: "…",
+    border: symbols::border::PLAIN,
+    scrollbar: symbols::scrollbar::DOUBLE_VERTICAL,
+};
+
+const ASCII_GLYPHS: Glyphs = Glyphs {
+    folder: "[D]",
+    file: "[F]",
+    check: "OK",
+    cross: "!!",
+    warning: "!",
+    arrow: "->",
+    up_down: "Up/Down",
+    shift_up_down: "Shift+Up/Down",
+    busy: "*",
+    done: "*",
+    ellipsis: "...",
+    border: symbols::border::Set {
+        top_left: "+",
+        top_right: "+",
+        bottom_left: "+",
+        bottom_right: "+",
+        vertical_left: "|",
+        vertical_right: "|",
+        horizontal_top: "-",
+        horizontal_bottom: "-",
+    },
+    scrollbar: symbols::scrollbar::Set {
+        track: "|",
+        thumb: "#",
+        begin: "^",
+        end: "v",
+    },
+};
+
+fn glyphs() -> &'static Glyphs {
+    if *ASCII_OUTPUT.get().unwrap_or(&false) {
+        &ASCII_GLYPHS
+    } else {
+        &UNICODE_GLYPHS
     }
+}
 
-    None
+/// Whether the locale promises UTF-8; the first of `LC_ALL`, `LC_CTYPE` and
+/// `LANG` that is set decides, like in libc.
+fn unicode_locale() -> bool {
+    ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .find_map(|var| env::var(var).ok().filter(|value| !value.is_empty()))
+        .is_some_and(|locale| {
+            let locale = locale.to_lowercase();
+            locale.contains("utf-8") || locale.contains("utf8")
+        })
 }
 
-trait PathExt {
-    fn readable(&self) -> bool;
+/// A block with borders drawn from the current glyphs.
+fn bordered_block() -> Block<'static> {
+    Block::default()
+        .borders(Borders::ALL)
+        .border_set(glyphs().border)
 }
 
-impl PathExt for Path {
-    fn readable(&self) -> bool {
-        match std::fs::metadata(self) {
-            Ok(metadata) => {
-                // Simple check - if we can read metadata, we likely have read access
-                // For a more thorough check, we'd need to check file permissions
-                !metadata.permissions().readonly()
-            }
-            Err(_) => false,
-        }
+/// The user whose configuration is read, for display.
+fn source_user() -> String {
+    USER_OVERRIDE
+        .get()
+        .cloned()
+        .or_else(|| env::var("SUDO_USER").ok())
+        .or_else(|| env::var("USER").ok())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Home of whoever runs kde-copycat, where output and logs go. Differs from
+/// `home_dir` with `--user`, whose home is only read from.
+fn own_home_dir() -> Option<std::path::PathBuf> {
+    match (HOME_OVERRIDE.get(), USER_OVERRIDE.get()) {
+        (Some(home), None) => Some(home.clone()),
+        _ => dirs::home_dir(),
     }
 }
 
-fn expand_tilde(path: &str) -> std::path::PathBuf {
-    if path.starts_with("~/") {
-        // Get the real user's home directory
-        let home = get_user_home_dir();
-        return home.join(&path[2..]);
-    } else if path == "~" {
-        let home = get_user_home_dir();
-        return home;
+/// The home whose configuration is read and copied: the one passed with
+/// `--home` or `--user`, or else the invoking user's (see `find_user_home`),
+/// so detection looks at the same files as the copy, also under sudo.
+fn home_dir() -> Option<std::path::PathBuf> {
+    if let Some(home) = HOME_OVERRIDE.get() {
+        return Some(home.clone());
     }
+    USER_HOME
+        .get_or_init(find_user_home)
+        .as_ref()
+        .map(|(home, _)| home.clone())
+}
 
-    // Handle relative paths by making them absolute to current directory
-    let path_buf = std::path::PathBuf::from(path);
-    if path_buf.is_relative() {
-        if let Ok(current_dir) = std::env::current_dir() {
-            return current_dir.join(path_buf);
-        }
+/// Where `home_dir` came from, for display: the option it was passed with or
+/// what `find_user_home` used.
+fn home_origin() -> String {
+    if let Some(user) = USER_OVERRIDE.get() {
+        return format!("--user {}", user);
+    }
+    if HOME_OVERRIDE.get().is_some() {
+        return "--home".to_string();
+    }
+    match USER_HOME.get_or_init(find_user_home) {
+        Some((_, origin)) => origin.to_string(),
+        None => "not found".to_string(),
     }
+}
 
-    path_buf
+static EFFECTIVE_UID: std::sync::OnceLock<Option<u32>> = std::sync::OnceLock::new();
+
+/// The effective uid from `id -u`, or `None` when it can't be run.
+fn effective_uid() -> Option<u32> {
+    *EFFECTIVE_UID.get_or_init(|| {
+        let output = run_command("id", &["-u"]).filter(|output| output.status.success())?;
+        String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+    })
+}
+
+/// An absolute directory from the XDG variable `var`. Ignored with `--home`,
+/// since the variables then belong to a different user than the files.
+fn xdg_env_dir(var: &str) -> Option<std::path::PathBuf> {
+    if HOME_OVERRIDE.get().is_some() {
+        return None;
+    }
+    env::var_os(var)
+        .map(std::path::PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+}
+
+/// `$XDG_CONFIG_HOME`, or `~/.config`.
+fn config_dir() -> Option<std::path::PathBuf> {
+    xdg_env_dir("XDG_CONFIG_HOME").or_else(|| Some(home_dir()?.join(".config")))
 }
 
+/// `$XDG_DATA_HOME`, or `~/.local/share`.
+fn data_dir() -> Option<std::path::PathBuf> {
+    xdg_env_dir("XDG_DATA_HOME").or_else(|| Some(home_dir()?.join(".local/share")))
+}
+
+/// `home_dir`, falling back to the current directory.
 fn get_user_home_dir() -> std::path::PathBuf {
+    home_dir().unwrap_or_else(|| {
+        std::env::current_dir().unwrap_or_else(|_| std::path::PathBuf::from("."))
+    })
+}
+
+/// The invoking user's home: `SUDO_USER`'s under sudo, then `$HOME` unless it
+/// is root's, then `$USER`'s passwd entry, then the first folder in `/home`.
+fn find_user_home() -> Option<(std::path::PathBuf, &'static str)> {
     // CRITICAL: Always prioritize SUDO_USER to get original user when running with sudo
     if let Ok(sudo_user) = std::env::var("SUDO_USER") {
-        let home = std::path::PathBuf::from("/home").join(&sudo_user);
-        if home.exists() {
-            return home;
+        let home = std::env::var("SUDO_UID")
+            .ok()
+            .and_then(|uid| lookup_passwd_home(&uid))
+            .or_else(|| lookup_passwd_home(&sudo_user));
+        if let Some(home) = home.filter(|home| home.exists()) {
+            return Some((home, "SUDO_USER"));
         }
     }
 
@@ -1436,16 +8711,17 @@ fn get_user_home_dir() -> std::path::PathBuf {
         let home_path = std::path::PathBuf::from(&home);
         // Don't use root's home directory
         if !home_path.ends_with("/root") && home_path.exists() {
-            return home_path;
+            return Some((home_path, "HOME"));
         }
     }
 
-    // Try to get the current user and construct their home directory
+    // Try to get the current user and look up their home directory
     if let Ok(username) = std::env::var("USER") {
         if username != "root" {
-            let home = std::path::PathBuf::from("/home").join(&username);
-            if home.exists() {
-                return home;
+            if let Some(home) = lookup_passwd_home(&username) {
+                if home.exists() {
+                    return Some((home, "USER"));
+                }
             }
         }
     }
@@ -1458,7 +8734,7 @@ fn get_user_home_dir() -> std::path::PathBuf {
                     let path = entry.path();
                     if let Some(name) = path.file_name() {
                         if name != "root" {
-                            return path;
+                            return Some((path, "/home"));
                         }
                     }
                 }
@@ -1466,6 +8742,366 @@ fn get_user_home_dir() -> std::path::PathBuf {
         }
     }
 
-    // Ultimate fallback: current directory
-    std::env::current_dir().unwrap_or_else(|_| std::path::PathBuf::from("."))
+    None
+}
+
+/// The fields of a passwd(5) line needed to resolve a home directory.
+struct PasswdEntry<'a> {
+    name: &'a str,
+    uid: &'a str,
+    home: &'a str,
+}
+
+fn parse_passwd_line(line: &str) -> Option<PasswdEntry<'_>> {
+    let fields: Vec<&str> = line.split(':').collect();
+    if fields.len() < 7 {
+        return None;
+    }
+    Some(PasswdEntry {
+        name: fields[0],
+        uid: fields[2],
+        home: fields[5],
+    })
+}
+
+/// Finds the home directory of a user name or numeric uid in `/etc/passwd`,
+/// falling back to `getent passwd` for accounts served by NSS (LDAP,
+/// systemd-homed, SSSD).
+fn lookup_passwd_home(user: &str) -> Option<std::path::PathBuf> {
+    let find = |content: &str| {
+        content
+            .lines()
+            .filter_map(parse_passwd_line)
+            .find(|entry| entry.name == user || entry.uid == user)
+            .filter(|entry| !entry.home.is_empty())
+            .map(|entry| std::path::PathBuf::from(entry.home))
+    };
+
+    if let Some(home) = fs::read_to_string("/etc/passwd")
+        .ok()
+        .and_then(|content| find(&content))
+    {
+        return Some(home);
+    }
+
+    let output = run_command("getent", &["passwd", user])?;
+    if !output.status.success() {
+        return None;
+    }
+    find(&String::from_utf8_lossy(&output.stdout))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh folder under the system temp folder, removed when dropped.
+    struct TempDir(std::path::PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path =
+                env::temp_dir().join(format!("kde-copycat-test-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn check_source_location_refuses_nested_folders() {
+        let tmp = TempDir::new("location");
+        let root = &tmp.0;
+        for dir in ["home/.themes", "out/T/GTK_Themes", "elsewhere"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        std::os::unix::fs::symlink(root.join("home"), root.join("home-link")).unwrap();
+        // What --link leaves behind: the source is a link into the theme
+        std::os::unix::fs::symlink(root.join("out/T/GTK_Themes"), root.join("home/linked"))
+            .unwrap();
+
+        let cases = [
+            // Theme folder inside the source
+            ("home/.themes/T", "home/.themes", false),
+            ("home/T", "home", false),
+            // Source inside the theme folder
+            ("out/T", "out/T/GTK_Themes", false),
+            ("out", "out/T", false),
+            // Through a symlinked parent
+            ("home-link/.themes/T", "home/.themes", false),
+            ("out/T", "home-link/../out/T/GTK_Themes", false),
+            // A linked source points into the theme but isn't inside it
+            ("out/T", "home/linked", true),
+            ("elsewhere/T", "home/.themes", true),
+            ("out/T2", "out/T", true),
+        ];
+        for (theme_dir, source, allowed) in cases {
+            let result = check_source_location(&root.join(theme_dir), &root.join(source), "Test");
+            assert_eq!(result.is_ok(), allowed, "{} in {}", theme_dir, source);
+        }
+    }
+
+    #[test]
+    fn resolved_path_resolves_parents_but_not_the_last_component() {
+        let tmp = TempDir::new("resolved");
+        let root = &tmp.0;
+        fs::create_dir_all(root.join("real/dir")).unwrap();
+        std::os::unix::fs::symlink(root.join("real"), root.join("alias")).unwrap();
+        std::os::unix::fs::symlink(root.join("real/dir"), root.join("real/link")).unwrap();
+
+        let real = root.canonicalize().unwrap().join("real");
+        assert_eq!(resolved_path(&root.join("alias/dir")), real.join("dir"));
+        assert_eq!(resolved_path(&root.join("alias/link")), real.join("link"));
+        assert_eq!(
+            resolved_path(&root.join("alias/missing/x")),
+            real.join("missing/x")
+        );
+        assert_eq!(
+            resolved_path(&root.join("real/dir/../dir")),
+            real.join("dir")
+        );
+    }
+
+    #[test]
+    fn dedupe_files_links_identical_files_once() {
+        let tmp = TempDir::new("dedupe");
+        let root = &tmp.0;
+        fs::create_dir_all(root.join("a/b")).unwrap();
+        for (file, content) in [
+            ("a/one", "same"),
+            ("a/b/two", "same"),
+            ("three", "same"),
+            ("other", "diff"),
+            ("script", "same"),
+            ("empty", ""),
+            ("a/empty", ""),
+        ] {
+            fs::write(root.join(file), content).unwrap();
+        }
+        fs::set_permissions(root.join("script"), fs::Permissions::from_mode(0o755)).unwrap();
+
+        assert_eq!(dedupe_files(root).unwrap(), (2, 8));
+        let inode = |file: &str| fs::metadata(root.join(file)).unwrap().ino();
+        assert_eq!(inode("a/one"), inode("a/b/two"));
+        assert_eq!(inode("a/one"), inode("three"));
+        assert_ne!(inode("a/one"), inode("other"));
+        assert_ne!(inode("a/one"), inode("script"));
+        assert_ne!(inode("empty"), inode("a/empty"));
+        assert_eq!(fs::read_to_string(root.join("three")).unwrap(), "same");
+
+        assert_eq!(dedupe_files(root).unwrap(), (0, 0));
+    }
+
+    #[test]
+    fn expand_vars_replaces_known_variables() {
+        env::set_var("KDE_COPYCAT_TEST_DIR", "/srv/themes");
+        env::remove_var("KDE_COPYCAT_TEST_UNSET");
+        for (path, expanded) in [
+            ("${KDE_COPYCAT_TEST_DIR}/a", "/srv/themes/a"),
+            ("$KDE_COPYCAT_TEST_DIR/a", "/srv/themes/a"),
+            ("$KDE_COPYCAT_TEST_DIR-old", "/srv/themes-old"),
+            ("$KDE_COPYCAT_TEST_UNSET/a", "$KDE_COPYCAT_TEST_UNSET/a"),
+            ("${KDE_COPYCAT_TEST_UNSET}/a", "${KDE_COPYCAT_TEST_UNSET}/a"),
+            ("${KDE_COPYCAT_TEST_DIR/a", "${KDE_COPYCAT_TEST_DIR/a"),
+            ("/a/b$", "/a/b$"),
+            ("/a/$/b", "/a/$/b"),
+            ("~/plain", "~/plain"),
+        ] {
+            assert_eq!(expand_vars(path), expanded, "{}", path);
+        }
+    }
+
+    #[test]
+    fn export_name_separates_sources_with_the_same_name() {
+        let comp = ThemeComponent::new(
+            "Test",
+            vec![
+                "~/.local/share/themes/",
+                "/usr/share/themes/",
+                "~/.themes/",
+                "~/.config/gtk-3.0/",
+            ],
+            "",
+        );
+        for (source, name) in [
+            ("~/.local/share/themes/", ".local_share_themes"),
+            ("/usr/share/themes/", "usr_share_themes"),
+            ("~/.themes/", ".themes"),
+            ("~/.config/gtk-3.0/", "gtk-3.0"),
+        ] {
+            assert_eq!(comp.export_name(source), name, "{}", source);
+            assert_eq!(
+                comp.restore_source(name.as_ref()).map(String::as_str),
+                Some(source)
+            );
+        }
+        // Exports from before export_name used the plain name
+        assert_eq!(
+            comp.restore_source("themes".as_ref()).map(String::as_str),
+            Some("~/.local/share/themes/")
+        );
+        assert_eq!(comp.restore_source("other".as_ref()), None);
+
+        // The name doesn't depend on where the XDG folders are
+        let data_home = env::var_os("XDG_DATA_HOME");
+        env::set_var("XDG_DATA_HOME", "/srv/data");
+        let name = comp.export_name("~/.local/share/themes/");
+        match data_home {
+            Some(value) => env::set_var("XDG_DATA_HOME", value),
+            None => env::remove_var("XDG_DATA_HOME"),
+        }
+        assert_eq!(name, ".local_share_themes");
+    }
+
+    #[test]
+    fn copy_destination_keeps_the_path_below_the_root() {
+        let root = Path::new("/src/theme");
+        let destination = Path::new("/out/T/Icons/theme");
+        for (entry, expected) in [
+            ("/src/theme", "/out/T/Icons/theme"),
+            ("/src/theme/", "/out/T/Icons/theme"),
+            ("/src/theme/a/b.svg", "/out/T/Icons/theme/a/b.svg"),
+            ("/elsewhere/b.svg", "/out/T/Icons/theme/b.svg"),
+        ] {
+            assert_eq!(
+                copy_destination(root, Path::new(entry), destination),
+                Path::new(expected),
+                "{}",
+                entry
+            );
+        }
+    }
+
+    #[test]
+    fn panel_locations_reads_panel_containments() {
+        let appletsrc = "\
+[Containments][1]
+location=0
+plugin=org.kde.plasma.folder
+
+[Containments][2]
+location=4
+plugin=org.kde.panel
+
+[Containments][2][Applets][3]
+location=3
+plugin=org.kde.plasma.kickoff
+
+[Containments][7]
+plugin=org.kde.panel
+location=5
+
+[Containments][9]
+plugin=org.kde.panel
+";
+        assert_eq!(panel_locations(appletsrc), ["bottom", "left", "floating"]);
+        assert!(panel_locations("[Containments][1]\nplugin=org.kde.plasma.folder\n").is_empty());
+        assert!(panel_locations("").is_empty());
+    }
+
+    #[test]
+    fn zsh_theme_parses_the_last_assignment() {
+        let cases = [
+            ("ZSH_THEME=\"robbyrussell\"", Some("robbyrussell")),
+            ("ZSH_THEME='agnoster'", Some("agnoster")),
+            ("ZSH_THEME=bira # comment", Some("bira")),
+            ("ZSH_THEME=\"\"", Some("")),
+            ("  ZSH_THEME=\"indented\"", Some("indented")),
+            ("ZSH_THEME=\"a\"\nZSH_THEME=\"b\"", Some("b")),
+            ("ZSH_THEME=\"a\"\n# ZSH_THEME=\"b\"", Some("a")),
+            ("# ZSH_THEME=\"commented\"", None),
+            ("export PATH=$HOME/bin", None),
+        ];
+        for (zshrc, expected) in cases {
+            assert_eq!(zsh_theme(zshrc).as_deref(), expected, "{:?}", zshrc);
+        }
+    }
+
+    #[test]
+    fn zsh_plugins_parses_single_and_multi_line_lists() {
+        let cases: [(&str, &[&str]); 6] = [
+            ("plugins=(git docker)", &["git", "docker"]),
+            ("plugins=()", &[]),
+            (
+                "plugins=(\n  git\n  # old\n  fzf # fuzzy\n)",
+                &["git", "fzf"],
+            ),
+            (
+                "plugins=(git\n  sudo)\nsource $ZSH/oh-my-zsh.sh",
+                &["git", "sudo"],
+            ),
+            ("plugins=(git)\nplugins=(z)", &["z"]),
+            ("source $ZSH/oh-my-zsh.sh", &[]),
+        ];
+        for (zshrc, expected) in cases {
+            assert_eq!(zsh_plugins(zshrc), expected, "{:?}", zshrc);
+        }
+    }
+
+    #[test]
+    fn effect_plugins_reads_only_the_plugins_group() {
+        let kwinrc = "[Compositing]\nblurEnabled=true\n\n\
+                      [Plugins]\nblurEnabled=true\nwobblywindowsEnabled = false\n\
+                      slideEnabled=maybe\nkrohnkiteEnabled=true\n\n\
+                      [Effect-blur]\nBlurStrength=5\n";
+        assert_eq!(
+            effect_plugins(kwinrc),
+            [
+                ("blur".to_string(), true),
+                ("wobblywindows".to_string(), false),
+                ("krohnkite".to_string(), true),
+            ]
+        );
+        assert!(effect_plugins("[General]\nfooEnabled=true\n").is_empty());
+    }
+
+    #[test]
+    fn truncate_to_width_ends_in_an_ellipsis() {
+        let cases = [
+            ("Breeze", 10, "Breeze"),
+            ("Breeze", 6, "Breeze"),
+            ("Breeze Dark", 6, "Breez…"),
+            ("Breeze", 1, "…"),
+            ("Breeze", 0, ""),
+            ("Größe", 4, "Grö…"),
+        ];
+        for (text, width, expected) in cases {
+            assert_eq!(
+                truncate_to_width(text, width),
+                expected,
+                "{:?} {}",
+                text,
+                width
+            );
+        }
+    }
+
+    #[test]
+    fn wrap_detection_keeps_value_and_source_apart() {
+        let pair = |value: &str, source: &str| (value.to_string(), source.to_string());
+        let cases = [
+            ("Breeze", " file:x", 20, vec![pair("Breeze", " file:x")]),
+            (
+                "Breeze",
+                " cmd:y",
+                4,
+                vec![pair("Bree", ""), pair("ze", " c"), pair("", "md:y")],
+            ),
+            ("", "", 5, vec![]),
+        ];
+        for (value, source, width, expected) in cases {
+            assert_eq!(
+                wrap_detection(value, source, width),
+                expected,
+                "{:?}",
+                value
+            );
+        }
+    }
 }